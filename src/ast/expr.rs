@@ -9,7 +9,10 @@ use std::{
     },
 };
 
-use crate::token::{Token, Value};
+use crate::{
+    token::{Token, Value, Span},
+    ast::stmt::Func,
+};
 
 pub trait AcceptExprVisitor {
     fn accept<V: ExprVisitor>(&self, visitor: &mut V) -> V::Output;
@@ -30,6 +33,12 @@ pub trait ExprVisitor {
     fn visit_set_expr(&mut self, expr: &Set) -> Self::Output;
     fn visit_this_expr(&mut self, expr: &This) -> Self::Output;
     fn visit_super_expr(&mut self, expr: &Super) -> Self::Output;
+    fn visit_list_expr(&mut self, expr: &List) -> Self::Output;
+    fn visit_index_expr(&mut self, expr: &Index) -> Self::Output;
+    fn visit_set_index_expr(&mut self, expr: &SetIndex) -> Self::Output;
+    fn visit_map_expr(&mut self, expr: &Map) -> Self::Output;
+    fn visit_lambda_expr(&mut self, expr: &Lambda) -> Self::Output;
+    fn visit_interpolation_expr(&mut self, expr: &Interpolation) -> Self::Output;
 }
 
 #[derive(Clone, Debug)]
@@ -46,6 +55,12 @@ pub enum Expr {
     Set(Set),
     This(This),
     Super(Super),
+    List(List),
+    Index(Index),
+    SetIndex(SetIndex),
+    Map(Map),
+    Lambda(Lambda),
+    Interpolation(Interpolation),
 }
 
 impl AcceptExprVisitor for Expr {
@@ -63,6 +78,12 @@ impl AcceptExprVisitor for Expr {
             Expr::Set(expr) => visitor.visit_set_expr(expr),
             Expr::This(expr) => visitor.visit_this_expr(expr),
             Expr::Super(expr) => visitor.visit_super_expr(expr),
+            Expr::List(expr) => visitor.visit_list_expr(expr),
+            Expr::Index(expr) => visitor.visit_index_expr(expr),
+            Expr::SetIndex(expr) => visitor.visit_set_index_expr(expr),
+            Expr::Map(expr) => visitor.visit_map_expr(expr),
+            Expr::Lambda(expr) => visitor.visit_lambda_expr(expr),
+            Expr::Interpolation(expr) => visitor.visit_interpolation_expr(expr),
         }
     }
 }
@@ -78,17 +99,18 @@ impl Expr {
         })
     }
     
-    pub fn new_grouping(expr: Expr) -> Self {
+    pub fn new_grouping(expr: Expr, span: Span) -> Self {
         let id = ID_GENERATOR.generate_id();
         Expr::Grouping(Grouping {
             id,
             expr: Box::new(expr),
+            span,
         })
     }
 
-    pub fn new_literal(value: Arc<RwLock<Value>>) -> Self {
+    pub fn new_literal(value: Arc<RwLock<Value>>, span: Span) -> Self {
         let id = ID_GENERATOR.generate_id();
-        Expr::Literal(Literal { id, value })
+        Expr::Literal(Literal { id, value, span })
     }
 
     pub fn new_unary(operator: Rc<Token>, right: Expr) -> Self {
@@ -108,12 +130,13 @@ impl Expr {
         })
     }
 
-    pub fn new_assign(token: Rc<Token>, value: Expr) -> Self {
+    pub fn new_assign(token: Rc<Token>, value: Expr, update: Option<Update>) -> Self {
         let id = ID_GENERATOR.generate_id();
         Expr::Assign(Assign {
             id,
             token,
             value: Box::new(value),
+            update,
         })
     }
 
@@ -146,13 +169,15 @@ impl Expr {
         })
     }
 
-    pub fn new_set(object: Expr, token: Rc<Token>, value: Expr) -> Self {
+    pub fn new_set(object: Expr, token: Rc<Token>, value: Expr,
+        update: Option<Update>) -> Self {
         let id = ID_GENERATOR.generate_id();
         Expr::Set(Set{
             id,
             object: Box::new(object),
             token,
             value: Box::new(value),
+            update,
         })
     }
 
@@ -165,6 +190,72 @@ impl Expr {
         let id = ID_GENERATOR.generate_id();
         Expr::Super(Super {id, keyword, method})
     }
+
+    pub fn new_list(elements: Vec<Expr>, span: Span) -> Self {
+        let id = ID_GENERATOR.generate_id();
+        Expr::List(List { id, elements, span })
+    }
+
+    pub fn new_map(entries: Vec<(Expr, Expr)>, span: Span) -> Self {
+        let id = ID_GENERATOR.generate_id();
+        Expr::Map(Map { id, entries, span })
+    }
+
+    pub fn new_lambda(func: Func, span: Span) -> Self {
+        let id = ID_GENERATOR.generate_id();
+        Expr::Lambda(Lambda { id, func, span })
+    }
+
+    pub fn new_interpolation(parts: Vec<Expr>, span: Span) -> Self {
+        let id = ID_GENERATOR.generate_id();
+        Expr::Interpolation(Interpolation { id, parts, span })
+    }
+
+    pub fn new_index(object: Expr, bracket: Rc<Token>, index: Expr) -> Self {
+        let id = ID_GENERATOR.generate_id();
+        Expr::Index(Index {
+            id,
+            object: Box::new(object),
+            bracket,
+            index: Box::new(index),
+        })
+    }
+
+    pub fn new_set_index(object: Expr, bracket: Rc<Token>, index: Expr,
+        value: Expr, update: Option<Update>) -> Self {
+        let id = ID_GENERATOR.generate_id();
+        Expr::SetIndex(SetIndex {
+            id,
+            object: Box::new(object),
+            bracket,
+            index: Box::new(index),
+            value: Box::new(value),
+            update,
+        })
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary(expr) => expr.left.span().to(&expr.right.span()),
+            Expr::Grouping(expr) => expr.span.clone(),
+            Expr::Literal(expr) => expr.span.clone(),
+            Expr::Unary(expr) => expr.operator.span.to(&expr.right.span()),
+            Expr::Var(expr) => expr.token.span.clone(),
+            Expr::Assign(expr) => expr.token.span.to(&expr.value.span()),
+            Expr::Logical(expr) => expr.left.span().to(&expr.right.span()),
+            Expr::Call(expr) => expr.callee.span().to(&expr.paren.span),
+            Expr::Get(expr) => expr.object.span().to(&expr.token.span),
+            Expr::Set(expr) => expr.object.span().to(&expr.value.span()),
+            Expr::This(expr) => expr.keyword.span.clone(),
+            Expr::Super(expr) => expr.keyword.span.to(&expr.method.span),
+            Expr::List(expr) => expr.span.clone(),
+            Expr::Index(expr) => expr.object.span().to(&expr.bracket.span),
+            Expr::SetIndex(expr) => expr.object.span().to(&expr.value.span()),
+            Expr::Map(expr) => expr.span.clone(),
+            Expr::Lambda(expr) => expr.span.clone(),
+            Expr::Interpolation(expr) => expr.span.clone(),
+        }
+    }
 }
 
 lazy_static! {
@@ -200,12 +291,14 @@ pub struct Binary {
 pub struct Grouping {
     pub id: u64,
     pub expr: Box<Expr>, 
+    pub span: Span,
 }
 
 #[derive(Clone, Debug)]
 pub struct Literal {
     pub id: u64,
     pub value: Arc<RwLock<Value>>,
+    pub span: Span,
 }
 
 #[derive(Clone, Debug)]
@@ -226,6 +319,16 @@ pub struct Assign {
     pub id: u64,
     pub token: Rc<Token>,
     pub value: Box<Expr>,
+    pub update: Option<Update>,
+}
+
+//`a += b` and `a++` combine the target's current value with `value` using
+//`operator` instead of overwriting it. the target is only evaluated once,
+//and `a++` produces the value from before the update
+#[derive(Clone, Debug)]
+pub struct Update {
+    pub operator: Rc<Token>,
+    pub postfix: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -257,6 +360,7 @@ pub struct Set {
     pub object:  Box<Expr>,
     pub token: Rc<Token>,
     pub value: Box<Expr>,
+    pub update: Option<Update>,
 }
 
 #[derive(Clone, Debug)]
@@ -271,3 +375,54 @@ pub struct Super {
     pub keyword: Rc<Token>,
     pub method: Rc<Token>,
 }
+
+#[derive(Clone, Debug)]
+pub struct List {
+    pub id: u64,
+    pub elements: Vec<Expr>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub struct Map {
+    pub id: u64,
+    pub entries: Vec<(Expr, Expr)>,
+    pub span: Span,
+}
+
+//an anonymous function, either `fn (a) { ... }` or `|a| expr`. the arrow
+//form's body is a single return of its expression
+#[derive(Clone, Debug)]
+pub struct Lambda {
+    pub id: u64,
+    pub func: Func,
+    pub span: Span,
+}
+
+//"a ${b} c" joins the text of each part: the literal "a ", b and the
+//literal " c"
+#[derive(Clone, Debug)]
+pub struct Interpolation {
+    pub id: u64,
+    pub parts: Vec<Expr>,
+    pub span: Span,
+}
+
+//bracket is the closing ']' so errors point at the end of the index
+#[derive(Clone, Debug)]
+pub struct Index {
+    pub id: u64,
+    pub object: Box<Expr>,
+    pub bracket: Rc<Token>,
+    pub index: Box<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SetIndex {
+    pub id: u64,
+    pub object: Box<Expr>,
+    pub bracket: Rc<Token>,
+    pub index: Box<Expr>,
+    pub value: Box<Expr>,
+    pub update: Option<Update>,
+}
@@ -1,4 +1,5 @@
 pub mod expr;
 pub mod stmt;
 pub mod parser;
+pub mod printer;
 
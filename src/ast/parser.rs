@@ -4,12 +4,12 @@ use std::{
 };
 
 use crate::{
-    token::{Token, TokenType, Value},
+    token::{Token, TokenType, Value, Span},
     error::{ErrorStatus, ParseError},
     callables::callable::FuncType,
     ast::{
-        expr::Expr,
-        stmt::Stmt,
+        expr::{Expr, Update},
+        stmt::{Stmt, Func, Catch, Annotations},
     }, 
 };
 
@@ -46,27 +46,77 @@ impl<'a> Parser<'a> {
     fn declaration(&mut self) -> Result<Stmt, ParseError> {
         match self.peek().token_type {
             TokenType::Let => {self.advance(); self.let_declaration()},
-            TokenType::Fn => {self.advance(); self.func_declaration(FuncType::Function)},
+            //`fn (` starts an anonymous function in an expression statement
+            TokenType::Fn if !self.check_next(TokenType::OpenParen) => {
+                self.advance();
+                self.func_declaration(FuncType::Function)
+            },
             TokenType::Class => {self.advance(); self.class_declaration()},
+            TokenType::Import | TokenType::From => self.import_declaration(),
+            TokenType::Export => self.export_declaration(),
             _ => self.stmt(),
         }
     }
 
+    fn import_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.advance();
+        if keyword.token_type == TokenType::Import {
+            let path = self.consume(TokenType::String, "Expect module path after 'import'.")?;
+            self.consume(TokenType::As, "Expect 'as' after module path.")?;
+            let alias = self.consume(TokenType::Identifier, "Expect module name after 'as'.")?;
+            self.consume(TokenType::Semicolon, "Expect ';' after import.")?;
+            return Ok(Stmt::new_import(keyword, path, Some(alias), vec!()))
+        }
+
+        let path = self.consume(TokenType::String, "Expect module path after 'from'.")?;
+        self.consume(TokenType::Import, "Expect 'import' after module path.")?;
+        let mut names = vec!();
+        loop {
+            names.push(self.consume(TokenType::Identifier, "Expect name to import.")?);
+            if !self.try_consume_token(TokenType::Comma) {
+                break
+            }
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after import.")?;
+        Ok(Stmt::new_import(keyword, path, None, names))
+    }
+
+    fn export_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.advance();
+        let declaration = match self.peek().token_type {
+            TokenType::Let => {self.advance(); self.let_declaration()?},
+            TokenType::Fn => {self.advance(); self.func_declaration(FuncType::Function)?},
+            TokenType::Class => {self.advance(); self.class_declaration()?},
+            _ => return Err(
+                ParseError::new(self.peek(), "Expect declaration after 'export'.")
+            ),
+        };
+        Ok(Stmt::new_export(keyword, declaration))
+    }
+
     fn stmt(&mut self) -> Result<Stmt, ParseError> {
         match self.peek().token_type {
             TokenType::If => {self.advance(); self.if_stmt()},
             TokenType::Print => {self.advance(); self.print_stmt()},
-            TokenType::While => {self.advance(); self.while_stmt()},
-            TokenType::For => {self.advance(); self.for_stmt()},
+            TokenType::While => {self.advance(); self.while_stmt(None)},
+            TokenType::For => {self.advance(); self.for_stmt(None)},
             TokenType::Return => {self.advance(); self.return_stmt()},
+            TokenType::Break => {self.advance(); self.break_stmt()},
+            TokenType::Continue => {self.advance(); self.continue_stmt()},
+            TokenType::Throw => {self.advance(); self.throw_stmt()},
+            TokenType::Try => {self.advance(); self.try_stmt()},
+            TokenType::Identifier if self.check_next(TokenType::Colon) => self.labeled_stmt(),
             TokenType::OpenBrace => {
-                self.advance(); Ok(Stmt::new_block(self.block()?))
+                let brace = self.advance();
+                let stmts = self.block()?;
+                Ok(Stmt::new_block(stmts, self.span_from(&brace)))
             },
             _ => self.expr_stmt(),
         }
     }
 
     fn if_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
         self.consume(TokenType::OpenParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::CloseParen, 
@@ -77,25 +127,39 @@ impl<'a> Parser<'a> {
         if self.try_consume_token(TokenType::Else) {
             else_body = Some(self.stmt()?);
         }
-        Ok(Stmt::new_if(condition, body, else_body))
+        Ok(Stmt::new_if(condition, body, else_body, self.span_from(&keyword)))
     }
 
 
     fn print_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
         let value: Expr = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::new_print(value))
+        Ok(Stmt::new_print(value, self.span_from(&keyword)))
+    }
+
+    //a label names the loop that follows it: `outer: while (...) {...}`
+    fn labeled_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let label = self.advance();
+        self.advance();
+        match self.peek().token_type {
+            TokenType::While => {self.advance(); self.while_stmt(Some(label))},
+            TokenType::For => {self.advance(); self.for_stmt(Some(label))},
+            _ => Err(ParseError::new(self.peek(), "Expect a loop after label.")),
+        }
     }
 
-    fn while_stmt(&mut self) -> Result<Stmt, ParseError> {
+    fn while_stmt(&mut self, label: Option<Rc<Token>>) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
         self.consume(TokenType::OpenParen, "Expect '(' after while.")?;
         let condition = self.expression()?;
         self.consume(TokenType::CloseParen, "Expect ')' after condition")?;
         let body = self.stmt()?;
-        Ok(Stmt::new_while(condition, body))
+        Ok(Stmt::new_while(condition, body, None, label, self.span_from(&keyword)))
     }
 
-    fn for_stmt(&mut self) -> Result<Stmt, ParseError> {
+    fn for_stmt(&mut self, label: Option<Rc<Token>>) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
         self.consume(TokenType::OpenParen, "Expect '(' after 'for'.")?;
         let initializer = match self.peek().token_type {
             TokenType::Semicolon => {self.advance(); None},
@@ -104,7 +168,8 @@ impl<'a> Parser<'a> {
         };
 
         let condition = match self.check(TokenType::Semicolon) {
-            true => Expr::new_literal(Arc::new(RwLock::new(Value::Bool(true)))),
+            true => Expr::new_literal(
+                Arc::new(RwLock::new(Value::Bool(true))), keyword.span.clone()),
             false => self.expression()?,
         };
         self.consume(TokenType::Semicolon, 
@@ -117,26 +182,19 @@ impl<'a> Parser<'a> {
         self.consume(TokenType::CloseParen, 
                      "Expect ')' after for clauses.")?;
 
-        let mut body = self.stmt()?;
-
-        if let Some(increment) = increment {
-            body = Stmt::new_block(vec!(
-                body,
-                Stmt::new_stmt_expr(increment),
-            ));
-        }
-        
-        body = Stmt::new_while(condition, body);
+        let body = self.stmt()?;
+        let span = self.span_from(&keyword);
+        let mut body = Stmt::new_while(condition, body, increment, label, span.clone());
 
         if let Some(initializer) = initializer {
-            body = Stmt::new_block(vec!(initializer, body));
+            body = Stmt::new_block(vec!(initializer, body), span);
         }
         Ok(body)
     }
 
     fn return_stmt(&mut self) -> Result<Stmt, ParseError> {
         let keyword = self.previous();
-        let value = match self.try_consume_token(TokenType::Semicolon) {
+        let value = match self.check(TokenType::Semicolon) {
             true => None,
             false => Some(self.expression()?),
         };
@@ -144,6 +202,62 @@ impl<'a> Parser<'a> {
         Ok(Stmt::new_return(keyword, value))
     }
 
+    fn break_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
+        let label = self.loop_label()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after 'break'.")?;
+        Ok(Stmt::new_break(keyword, label))
+    }
+
+    fn continue_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
+        let label = self.loop_label()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after 'continue'.")?;
+        Ok(Stmt::new_continue(keyword, label))
+    }
+
+    fn throw_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after thrown value.")?;
+        Ok(Stmt::new_throw(keyword, value))
+    }
+
+    fn try_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous();
+        self.consume(TokenType::OpenBrace, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+
+        let catch = match self.try_consume_token(TokenType::Catch) {
+            true => {
+                self.consume(TokenType::OpenParen, "Expect '(' after 'catch'.")?;
+                let name = self.consume(TokenType::Identifier, "Expect error name.")?;
+                self.consume(TokenType::CloseParen, "Expect ')' after error name.")?;
+                self.consume(TokenType::OpenBrace, "Expect '{' before catch body.")?;
+                Some(Catch { name, body: self.block()? })
+            },
+            false => None,
+        };
+        let finally = match self.try_consume_token(TokenType::Finally) {
+            true => {
+                self.consume(TokenType::OpenBrace, "Expect '{' after 'finally'.")?;
+                Some(self.block()?)
+            },
+            false => None,
+        };
+        if catch.is_none() && finally.is_none() {
+            return Err(ParseError::new(self.peek(), "Expect 'catch' or 'finally' after try block."))
+        }
+        Ok(Stmt::new_try(keyword, body, catch, finally))
+    }
+
+    fn loop_label(&mut self) -> Result<Option<Rc<Token>>, ParseError> {
+        match self.check(TokenType::Identifier) {
+            true => Ok(Some(self.advance())),
+            false => Ok(None),
+        }
+    }
+
     fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut stmts: Vec<Stmt> = vec!();
         while !self.check(TokenType::CloseBrace) && !self.is_at_end() {
@@ -162,6 +276,7 @@ impl<'a> Parser<'a> {
     fn let_declaration(&mut self) -> Result<Stmt, ParseError> {
         let token = self
             .consume(TokenType::Identifier, "Expect variable name.")?;
+        let annotation = self.annotation()?;
 
         let mut initializer = None;
         if self.try_consume_token(TokenType::Equal) {
@@ -170,29 +285,91 @@ impl<'a> Parser<'a> {
 
         self.consume(TokenType::Semicolon, 
                      "Expect ';' after variable declaration.")?;
-        Ok(Stmt::new_let(token, initializer))
+        Ok(Stmt::new_let(token, annotation, initializer))
     }
 
     fn func_declaration(&mut self, func_type: FuncType) -> Result<Stmt, ParseError> {
         let message = format!("Expect {:?} name.", func_type);
         let token = self.consume(TokenType::Identifier, &message)?;
         self.consume(TokenType::OpenParen, "Expect '(' after function name.")?;
+        let (params, mut annotations) = self.params(TokenType::CloseParen)?;
+        self.consume(TokenType::CloseParen, "Expect ')' after parameters.")?;
+        annotations.returns = self.return_annotation()?;
+        let message = format!("Expect '{{' before {:?} body", func_type);
+        self.consume(TokenType::OpenBrace, &message)?;
+        let body = self.block()?;
+        Ok(Stmt::new_func(token, params, body, annotations))
+    }
 
+    //parameter names up to, but not including, the closing token
+    fn params(&mut self, closing: TokenType
+        ) -> Result<(Vec<Rc<Token>>, Annotations), ParseError> {
         let mut params = vec!();
-        while !self.check(TokenType::CloseParen) {
+        let mut annotations = Annotations::default();
+        while !self.check(closing.clone()) {
             if params.len() >= 255 {
                 return Err(ParseError::new(self.peek(), "Can't have more than 255 parameters."))
             }
             params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+            annotations.params.push(self.annotation()?);
             if !self.try_consume_token(TokenType::Comma) {
                 break
             }
         }
-        self.consume(TokenType::CloseParen, "Expect ')' after parameters.")?;
-        let message = format!("Expect '{{' before {:?} body", func_type);
-        self.consume(TokenType::OpenBrace, &message)?;
-        let body = self.block()?;
-        Ok(Stmt::new_func(token, params, body))
+        Ok((params, annotations))
+    }
+
+    //the optional `: Type` after a variable or parameter name
+    fn annotation(&mut self) -> Result<Option<Rc<Token>>, ParseError> {
+        match self.try_consume_token(TokenType::Colon) {
+            true => Ok(Some(self.type_name()?)),
+            false => Ok(None),
+        }
+    }
+
+    //the optional `-> Type` after a parameter list
+    fn return_annotation(&mut self) -> Result<Option<Rc<Token>>, ParseError> {
+        match self.try_consume_token(TokenType::Arrow) {
+            true => Ok(Some(self.type_name()?)),
+            false => Ok(None),
+        }
+    }
+
+    //`Nil` is a keyword, every other type is named by an identifier
+    fn type_name(&mut self) -> Result<Rc<Token>, ParseError> {
+        match self.check(TokenType::Nil) {
+            true => Ok(self.advance()),
+            false => self.consume(TokenType::Identifier, "Expect type name."),
+        }
+    }
+
+    //`fn (a, b) { ... }` or `|a, b| expr`, where the arrow form's body extends
+    //as far right as an expression can
+    fn lambda(&mut self, keyword: Rc<Token>) -> Result<Expr, ParseError> {
+        let (params, annotations, body) = match keyword.token_type {
+            TokenType::Fn => {
+                self.consume(TokenType::OpenParen, "Expect '(' after 'fn'.")?;
+                let (params, mut annotations) = self.params(TokenType::CloseParen)?;
+                self.consume(TokenType::CloseParen, "Expect ')' after parameters.")?;
+                annotations.returns = self.return_annotation()?;
+                self.consume(TokenType::OpenBrace, "Expect '{' before Function body")?;
+                (params, annotations, self.block()?)
+            },
+            _ => {
+                let (params, annotations) = self.params(TokenType::Pipe)?;
+                self.consume(TokenType::Pipe, "Expect '|' after parameters.")?;
+                let value = self.expression()?;
+                (params, annotations, vec!(Stmt::new_return(Rc::clone(&keyword), Some(value))))
+            },
+        };
+        let name = Token::new(
+            TokenType::Identifier,
+            Rc::new("lambda".to_string()),
+            Arc::new(RwLock::new(Value::None)),
+            keyword.span.clone(),
+        );
+        let func = Func { token: Rc::new(name), params, body, annotations };
+        Ok(Expr::new_lambda(func, self.span_from(&keyword)))
     }
 
     fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
@@ -215,6 +392,21 @@ impl<'a> Parser<'a> {
         Ok(class)
     }
  
+    //operators from loosest to tightest binding:
+    //  =                   assignment, right associative
+    //  or
+    //  and
+    //  == !=
+    //  < <= > >=
+    //  |                   bitwise or
+    //  ^                   bitwise xor
+    //  &                   bitwise and
+    //  << >>               shifts
+    //  + -
+    //  * / ~/ %            ~/ is integer division, since // starts a comment
+    //  ! -                 unary
+    //  **                  exponent, right associative so 2 ** 3 ** 2 is 2 ** 9
+    //  () . []             calls, properties and indexing
     fn expression(&mut self) -> Result<Expr, ParseError> {
         self.assignment()
     }
@@ -222,22 +414,58 @@ impl<'a> Parser<'a> {
     fn assignment(&mut self) -> Result<Expr, ParseError> {
         let expr = self.or()?;
 
+        if self.peek().token_type.compound_operator().is_some() {
+            let operator = self.advance();
+            let update = self.update(operator, false);
+            let value = self.assignment()?;
+            return self.assignment_target(expr, value, Some(update))
+        }
+
         if !self.try_consume_token(TokenType::Equal) {
             return Ok(expr)
         }
 
         let value: Expr = self.assignment()?;
+        self.assignment_target(expr, value, None)
+    }
 
-        match expr {
-            Expr::Var(var) => Ok(Expr::new_assign(var.token, value)),
-            Expr::Get(var) => Ok(Expr::new_set(*var.object, var.token, value)),
+    fn assignment_target(&mut self, target: Expr, value: Expr, update: Option<Update>
+        ) -> Result<Expr, ParseError> {
+        match target {
+            Expr::Var(var) => Ok(Expr::new_assign(var.token, value, update)),
+            Expr::Get(var) => Ok(Expr::new_set(*var.object, var.token, value, update)),
+            Expr::Index(index) => Ok(
+                Expr::new_set_index(*index.object, index.bracket, *index.index, value, update)
+            ),
             _ => {
-                let equals = self.previous();
-                Err(ParseError::new(equals, "Invalid assignment target."))
+                let operator = match update {
+                    Some(update) => update.operator,
+                    None => self.previous(),
+                };
+                Err(ParseError::new(operator, "Invalid assignment target."))
             }
         }
     }
 
+    //turns `+=` or `++` into the `+` it applies, keeping the original span
+    fn update(&self, token: Rc<Token>, postfix: bool) -> Update {
+        let operator = Token::new(
+            token.token_type.compound_operator().expect("caller checked for an update"),
+            Rc::clone(&token.lexeme),
+            Arc::new(RwLock::new(Value::None)),
+            token.span.clone(),
+        );
+        Update { operator: Rc::new(operator), postfix }
+    }
+
+    //`x++` and `++x` add a literal one to their target
+    fn increment(&mut self, target: Expr, token: Rc<Token>, postfix: bool
+        ) -> Result<Expr, ParseError> {
+        let one = Expr::new_literal(Arc::new(RwLock::new(Value::Num(1.0))), token.span.clone());
+        let update = self.update(token, postfix);
+        self.assignment_target(target, one, Some(update))
+    }
+
     fn or(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.and()?;
 
@@ -276,12 +504,55 @@ impl<'a> Parser<'a> {
     }
 
     fn comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.term()?;
+        let mut expr = self.bit_or()?;
         while self.try_consume_tokens(vec!(
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
+        )) {
+            let operator = self.previous();
+            let right = self.bit_or()?;
+            expr = Expr::new_binary(expr, operator, right);
+        }
+        Ok(expr)
+    }
+
+    fn bit_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.bit_xor()?;
+        while self.try_consume_token(TokenType::Pipe) {
+            let operator = self.previous();
+            let right = self.bit_xor()?;
+            expr = Expr::new_binary(expr, operator, right);
+        }
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.bit_and()?;
+        while self.try_consume_token(TokenType::Caret) {
+            let operator = self.previous();
+            let right = self.bit_and()?;
+            expr = Expr::new_binary(expr, operator, right);
+        }
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.shift()?;
+        while self.try_consume_token(TokenType::Ampersand) {
+            let operator = self.previous();
+            let right = self.shift()?;
+            expr = Expr::new_binary(expr, operator, right);
+        }
+        Ok(expr)
+    }
+
+    fn shift(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.term()?;
+        while self.try_consume_tokens(vec!(
+            TokenType::LessLess,
+            TokenType::GreaterGreater,
         )) {
             let operator = self.previous();
             let right = self.term()?;
@@ -308,6 +579,8 @@ impl<'a> Parser<'a> {
         while self.try_consume_tokens(vec!(
             TokenType::Star,
             TokenType::Slash,
+            TokenType::TildeSlash,
+            TokenType::Percent,
         )) {
             let operator = self.previous();
             let right = self.unary()?;
@@ -325,7 +598,26 @@ impl<'a> Parser<'a> {
             let right = self.unary()?;
             return Ok(Expr::new_unary(operator, right))
         }
-        self.call()
+        if self.try_consume_tokens(vec!(
+            TokenType::PlusPlus,
+            TokenType::MinusMinus,
+        )) {
+            let operator = self.previous();
+            let target = self.unary()?;
+            return self.increment(target, operator, false)
+        }
+        self.power()
+    }
+
+    //the exponent may itself be negated: 2 ** -1
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.call()?;
+        if self.try_consume_token(TokenType::StarStar) {
+            let operator = self.previous();
+            let right = self.unary()?;
+            return Ok(Expr::new_binary(expr, operator, right))
+        }
+        Ok(expr)
     }
 
     fn call(&mut self) -> Result<Expr, ParseError> {
@@ -339,36 +631,69 @@ impl<'a> Parser<'a> {
                     "Expect property name after '.'")?;
                 expr = Expr::new_get(expr, token);
             } 
+            else if self.try_consume_token(TokenType::OpenBracket) {
+                let index = self.expression()?;
+                let bracket = self.consume(TokenType::CloseBracket,
+                    "Expect ']' after index.")?;
+                expr = Expr::new_index(expr, bracket, index);
+            }
             else {break}
         }
+        if self.try_consume_tokens(vec!(
+            TokenType::PlusPlus,
+            TokenType::MinusMinus,
+        )) {
+            let operator = self.previous();
+            return self.increment(expr, operator, true)
+        }
         Ok(expr)
     }
 
     fn primary(&mut self) -> Result<Expr, ParseError> {
-        let expr = match self.advance().token_type {
+        let token = self.advance();
+        let span = token.span.clone();
+        let expr = match token.token_type {
             TokenType::False => 
-                Expr::new_literal(Arc::new(RwLock::new(Value::Bool(false)))),
+                Expr::new_literal(Arc::new(RwLock::new(Value::Bool(false))), span),
                 
             TokenType::True => 
-                Expr::new_literal(Arc::new(RwLock::new(Value::Bool(true)))),
+                Expr::new_literal(Arc::new(RwLock::new(Value::Bool(true))), span),
 
             TokenType::Nil => 
-                Expr::new_literal(Arc::new(RwLock::new(Value::None))),
+                Expr::new_literal(Arc::new(RwLock::new(Value::None)), span),
 
             TokenType::Number | TokenType::String =>
-                Expr::new_literal(self.previous().literal.clone()),
+                Expr::new_literal(token.literal.clone(), span),
+
+            TokenType::Interpolation => self.interpolation(token)?,
 
             TokenType::Identifier => 
-                Expr::new_var(self.previous()),
+                Expr::new_var(token),
 
-            TokenType::OpenParen => 
-                Expr::new_grouping(self.grouping()?),
+            TokenType::OpenParen => {
+                let expr = self.grouping()?;
+                Expr::new_grouping(expr, self.span_from(&token))
+            },
 
             TokenType::This => 
                 Expr::new_this(self.previous()),
 
             TokenType::Super => self.new_super()?,
 
+            TokenType::Fn | TokenType::Pipe => self.lambda(token)?,
+
+            TokenType::OpenBracket => {
+                let elements = self.list_elements()?;
+                Expr::new_list(elements, self.span_from(&token))
+            },
+
+            //a brace only starts a map in expression position, statements
+            //starting with one are blocks
+            TokenType::OpenBrace => {
+                let entries = self.map_entries()?;
+                Expr::new_map(entries, self.span_from(&token))
+            },
+
             _ => return Err(
                 ParseError::new(self.previous(), "Expected expression.")
             ),
@@ -376,6 +701,64 @@ impl<'a> Parser<'a> {
         Ok(expr) 
     }
 
+    //"a ${b} c" scans as the segments `"a ${`, `} c"` with b between them.
+    //segments with no text, like the ones around "${b}", are left out
+    fn interpolation(&mut self, first: Rc<Token>) -> Result<Expr, ParseError> {
+        let mut parts = vec!();
+        text_part(&mut parts, &first);
+        loop {
+            parts.push(self.expression()?);
+
+            let segment = self.advance();
+            let is_last = match segment.token_type {
+                TokenType::String => true,
+                TokenType::Interpolation => false,
+                _ => return Err(ParseError::new(segment,
+                    "Expect '}' after interpolated expression.")),
+            };
+            text_part(&mut parts, &segment);
+            if is_last {
+                return Ok(Expr::new_interpolation(parts, self.span_from(&first)))
+            }
+        }
+    }
+
+    fn list_elements(&mut self) -> Result<Vec<Expr>, ParseError> {
+        let mut elements = vec!();
+        while !self.check(TokenType::CloseBracket) {
+            elements.push(self.expression()?);
+            if elements.len() > 255 {
+                return Err(ParseError::new(self.peek(),
+                               "Can't have more than 255 elements in a list literal.")
+                )
+            }
+            if !self.try_consume_token(TokenType::Comma) {
+                break
+            }
+        }
+        self.consume(TokenType::CloseBracket, "Expect ']' after list elements.")?;
+        Ok(elements)
+    }
+
+    fn map_entries(&mut self) -> Result<Vec<(Expr, Expr)>, ParseError> {
+        let mut entries = vec!();
+        while !self.check(TokenType::CloseBrace) {
+            let key = self.expression()?;
+            self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+            entries.push((key, self.expression()?));
+            if entries.len() > 255 {
+                return Err(ParseError::new(self.peek(),
+                               "Can't have more than 255 entries in a map literal.")
+                )
+            }
+            if !self.try_consume_token(TokenType::Comma) {
+                break
+            }
+        }
+        self.consume(TokenType::CloseBrace, "Expect '}' after map entries.")?;
+        Ok(entries)
+    }
+
     fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
         let mut args = vec!();
         while !self.check(TokenType::CloseParen) {
@@ -442,6 +825,13 @@ impl<'a> Parser<'a> {
         self.peek().token_type == token_type
     }
 
+    fn check_next(&self, token_type: TokenType) -> bool {
+        match self.tokens.get(self.curr + 1) {
+            Some(token) => token.token_type == token_type,
+            None => false,
+        }
+    }
+
     fn is_at_end(&self) -> bool {
         self.peek().token_type == TokenType::Eof
     }
@@ -459,6 +849,11 @@ impl<'a> Parser<'a> {
         Rc::clone(&self.tokens[self.curr - 1])
     }
 
+    //from the start of `first` to the end of the last consumed token
+    fn span_from(&self, first: &Token) -> Span {
+        first.span.to(&self.previous().span)
+    }
+
     //jumps to start of next statement
     fn synchronize(&mut self) {
         while !(self.is_at_end()) {
@@ -474,11 +869,25 @@ impl<'a> Parser<'a> {
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => return,
+                | TokenType::Return
+                | TokenType::Break
+                | TokenType::Continue
+                | TokenType::Throw
+                | TokenType::Try
+                | TokenType::Import
+                | TokenType::From
+                | TokenType::Export => return,
                 _ => continue,
             }
         };
     }
 }
 
-
+fn text_part(parts: &mut Vec<Expr>, segment: &Token) {
+    if let Value::String(text) = &*segment.literal.read().unwrap() {
+        if text.is_empty() {
+            return
+        }
+    }
+    parts.push(Expr::new_literal(segment.literal.clone(), segment.span.clone()));
+}
@@ -0,0 +1,407 @@
+use std::{
+    collections::HashMap,
+    rc::Rc,
+    str::FromStr,
+};
+
+use crate::{
+    ast::{
+        expr::{self, Expr, AcceptExprVisitor, ExprVisitor},
+        stmt::{self, Stmt, AcceptStmtVisitor, StmtVisitor},
+    },
+    token::{Token, Value},
+    json,
+};
+
+/// How `Lax::dump_ast` writes the syntax tree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AstFormat {
+    Tree,
+    Sexpr,
+    Json,
+}
+
+impl FromStr for AstFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "tree" => Ok(AstFormat::Tree),
+            "sexpr" => Ok(AstFormat::Sexpr),
+            "json" => Ok(AstFormat::Json),
+            _ => Err(format!("Unknown AST format '{}', expected 'tree', 'sexpr' or 'json'.", name)),
+        }
+    }
+}
+
+//a syntax tree reduced to labels, so it can be shown without the spans and
+//tokens the rest of the compiler needs
+pub struct Node {
+    pub label: String,
+    pub children: Vec<Node>,
+    pub binding: Option<Binding>,
+}
+
+//where the resolver found a name: how many scopes out from where it's used,
+//or None for a global. keyed by the id of the expression using it
+pub struct Binding {
+    pub id: u64,
+    pub depth: Option<usize>,
+}
+
+impl Binding {
+    fn depth(&self) -> String {
+        match self.depth {
+            Some(depth) => format!("depth {}", depth),
+            None => "global".to_string(),
+        }
+    }
+}
+
+impl Node {
+    fn new(label: &str, children: Vec<Node>) -> Self {
+        Node { label: label.to_string(), children, binding: None }
+    }
+
+    fn leaf(label: &str) -> Self {
+        Node::new(label, vec!())
+    }
+
+    fn bound(mut self, binding: Option<Binding>) -> Self {
+        self.binding = binding;
+        self
+    }
+
+    //one node per line, children indented under their parent:
+    //  Print
+    //    Binary +
+    //      Literal 1
+    //      Literal 2
+    pub fn tree(&self) -> String {
+        let mut out = String::new();
+        self.write_tree(0, &mut out);
+        out
+    }
+
+    fn write_tree(&self, depth: usize, out: &mut String) {
+        out.push_str(&"  ".repeat(depth));
+        out.push_str(&self.label);
+        if let Some(binding) = &self.binding {
+            out.push_str(&format!(" [#{} {}]", binding.id, binding.depth()));
+        }
+        out.push('\n');
+        for child in &self.children {
+            child.write_tree(depth + 1, out);
+        }
+    }
+
+    //`(Binary + (Literal 1) (Var x :id 3 :depth 0))`
+    pub fn sexpr(&self) -> String {
+        let mut parts = vec!(self.label.clone());
+        if let Some(binding) = &self.binding {
+            let depth = binding.depth.map_or("global".to_string(), |depth| depth.to_string());
+            parts.push(format!(":id {} :depth {}", binding.id, depth));
+        }
+        parts.extend(self.children.iter().map(Node::sexpr));
+        format!("({})", parts.join(" "))
+    }
+
+    //`{"label":"Var x","id":3,"depth":0,"children":[]}`, with a null depth
+    //for globals and no id or depth on nodes that don't name a variable
+    pub fn json(&self) -> String {
+        let binding = match &self.binding {
+            Some(binding) => {
+                let depth = binding.depth.map_or("null".to_string(), |depth| depth.to_string());
+                format!(",\"id\":{},\"depth\":{}", binding.id, depth)
+            },
+            None => String::new(),
+        };
+        let children: Vec<String> = self.children.iter().map(Node::json).collect();
+        format!("{{\"label\":{}{},\"children\":[{}]}}",
+            json::quote(&self.label), binding, children.join(","))
+    }
+}
+
+//turns statements into nodes. given the depths the resolver worked out,
+//names are labeled with where they were found
+pub struct AstPrinter<'a> {
+    depths: Option<&'a HashMap<u64, usize>>,
+}
+
+impl<'a> AstPrinter<'a> {
+    pub fn nodes(stmts: &[Stmt]) -> Vec<Node> {
+        AstPrinter { depths: None }.stmts(stmts)
+    }
+
+    pub fn resolved(stmts: &[Stmt], depths: &'a HashMap<u64, usize>) -> Vec<Node> {
+        AstPrinter { depths: Some(depths) }.stmts(stmts)
+    }
+
+    pub fn tree(stmts: &[Stmt]) -> String {
+        AstPrinter::nodes(stmts).iter().map(Node::tree).collect()
+    }
+
+    pub fn render(nodes: &[Node], format: AstFormat) -> String {
+        match format {
+            AstFormat::Tree => nodes.iter().map(Node::tree).collect(),
+            AstFormat::Sexpr => nodes.iter().map(|node| node.sexpr() + "\n").collect(),
+            AstFormat::Json => {
+                let nodes: Vec<String> = nodes.iter().map(Node::json).collect();
+                format!("[{}]\n", nodes.join(","))
+            },
+        }
+    }
+
+    fn binding(&self, id: u64) -> Option<Binding> {
+        self.depths.map(|depths| Binding { id, depth: depths.get(&id).copied() })
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Node {
+        expr.accept(self)
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Vec<Node> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Vec<Node> {
+        stmts.iter().map(|stmt| stmt.accept(self)).collect()
+    }
+
+    fn block(&mut self, label: &str, stmts: &[Stmt]) -> Node {
+        Node::new(label, self.stmts(stmts))
+    }
+}
+
+fn literal(value: &Value) -> String {
+    match value {
+        Value::String(value) => format!("{:?}", value),
+        value => value.to_string(),
+    }
+}
+
+fn names(names: &[Rc<Token>]) -> String {
+    let names: Vec<&str> = names.iter().map(|name| name.lexeme.as_str()).collect();
+    format!("({})", names.join(", "))
+}
+
+//`(a: Str, b) -> Bool`, leaving out annotations that weren't written
+fn signature(func: &stmt::Func) -> String {
+    let params: Vec<String> = func.params.iter().zip(&func.annotations.params)
+        .map(|(param, annotation)| format!("{}{}", param.lexeme, annotated(annotation)))
+        .collect();
+    match &func.annotations.returns {
+        Some(returns) => format!("({}) -> {}", params.join(", "), returns.lexeme),
+        None => format!("({})", params.join(", ")),
+    }
+}
+
+fn annotated(annotation: &Option<Rc<Token>>) -> String {
+    match annotation {
+        Some(annotation) => format!(": {}", annotation.lexeme),
+        None => String::new(),
+    }
+}
+
+//`a += 1` shows as `+=` and `a++` as `++ postfix`
+fn update(update: &Option<expr::Update>) -> String {
+    match update {
+        None => String::new(),
+        Some(update) if update.postfix => {
+            format!(" {}{} postfix", update.operator.lexeme, update.operator.lexeme)
+        },
+        Some(update) => format!(" {}=", update.operator.lexeme),
+    }
+}
+
+fn label(keyword: &str, label: &Option<Rc<Token>>) -> String {
+    match label {
+        Some(label) => format!("{} {}", keyword, label.lexeme),
+        None => keyword.to_string(),
+    }
+}
+
+impl ExprVisitor for AstPrinter<'_> {
+    type Output = Node;
+
+    fn visit_binary_expr(&mut self, expr: &expr::Binary) -> Node {
+        let children = vec!(self.expr(&expr.left), self.expr(&expr.right));
+        Node::new(&format!("Binary {}", expr.operator.lexeme), children)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &expr::Grouping) -> Node {
+        Node::new("Grouping", vec!(self.expr(&expr.expr)))
+    }
+
+    fn visit_literal_expr(&mut self, expr: &expr::Literal) -> Node {
+        Node::leaf(&format!("Literal {}", literal(&expr.value.read().unwrap())))
+    }
+
+    //interpolation is lowered to a `str` operator keyed by the string segment
+    fn visit_unary_expr(&mut self, expr: &expr::Unary) -> Node {
+        Node::new(&format!("Unary {}", expr.operator.lexeme), vec!(self.expr(&expr.right)))
+    }
+
+    fn visit_var_expr(&mut self, expr: &expr::Var) -> Node {
+        Node::leaf(&format!("Var {}", expr.token.lexeme)).bound(self.binding(expr.id))
+    }
+
+    fn visit_assign_expr(&mut self, expr: &expr::Assign) -> Node {
+        let label = format!("Assign {}{}", expr.token.lexeme, update(&expr.update));
+        Node::new(&label, vec!(self.expr(&expr.value))).bound(self.binding(expr.id))
+    }
+
+    fn visit_logical_expr(&mut self, expr: &expr::Logical) -> Node {
+        let children = vec!(self.expr(&expr.left), self.expr(&expr.right));
+        Node::new(&format!("Logical {}", expr.operator.lexeme), children)
+    }
+
+    fn visit_call_expr(&mut self, expr: &expr::Call) -> Node {
+        let mut children = vec!(self.expr(&expr.callee));
+        children.extend(self.exprs(&expr.args));
+        Node::new("Call", children)
+    }
+
+    fn visit_get_expr(&mut self, expr: &expr::Get) -> Node {
+        Node::new(&format!("Get {}", expr.token.lexeme), vec!(self.expr(&expr.object)))
+    }
+
+    fn visit_set_expr(&mut self, expr: &expr::Set) -> Node {
+        let label = format!("Set {}{}", expr.token.lexeme, update(&expr.update));
+        Node::new(&label, vec!(self.expr(&expr.object), self.expr(&expr.value)))
+    }
+
+    fn visit_this_expr(&mut self, expr: &expr::This) -> Node {
+        Node::leaf("This").bound(self.binding(expr.id))
+    }
+
+    fn visit_super_expr(&mut self, expr: &expr::Super) -> Node {
+        Node::leaf(&format!("Super {}", expr.method.lexeme)).bound(self.binding(expr.id))
+    }
+
+    fn visit_list_expr(&mut self, expr: &expr::List) -> Node {
+        Node::new("List", self.exprs(&expr.elements))
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &expr::Interpolation) -> Node {
+        Node::new("Interpolation", self.exprs(&expr.parts))
+    }
+
+    fn visit_index_expr(&mut self, expr: &expr::Index) -> Node {
+        Node::new("Index", vec!(self.expr(&expr.object), self.expr(&expr.index)))
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &expr::SetIndex) -> Node {
+        let children = vec!(
+            self.expr(&expr.object),
+            self.expr(&expr.index),
+            self.expr(&expr.value),
+        );
+        Node::new(&format!("SetIndex{}", update(&expr.update)), children)
+    }
+
+    fn visit_map_expr(&mut self, expr: &expr::Map) -> Node {
+        let entries = expr.entries.iter()
+            .map(|(key, value)| Node::new("Entry", vec!(self.expr(key), self.expr(value))))
+            .collect();
+        Node::new("Map", entries)
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &expr::Lambda) -> Node {
+        let label = format!("Lambda {}", signature(&expr.func));
+        self.block(&label, &expr.func.body)
+    }
+}
+
+impl StmtVisitor for AstPrinter<'_> {
+    type Output = Node;
+
+    fn visit_expr_stmt(&mut self, stmt: &stmt::StmtExpr) -> Node {
+        Node::new("Expr", vec!(self.expr(&stmt.expr)))
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &stmt::Print) -> Node {
+        Node::new("Print", vec!(self.expr(&stmt.expr)))
+    }
+
+    fn visit_let_stmt(&mut self, stmt: &stmt::Let) -> Node {
+        let children = stmt.initializer.iter().map(|expr| self.expr(expr)).collect();
+        let label = format!("Let {}{}", stmt.token.lexeme, annotated(&stmt.annotation));
+        Node::new(&label, children)
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &stmt::Block) -> Node {
+        self.block("Block", &stmt.stmts)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &stmt::If) -> Node {
+        let mut children = vec!(self.expr(&stmt.condition), stmt.body.accept(self));
+        if let Some(else_body) = &stmt.else_body {
+            children.push(Node::new("Else", vec!(else_body.accept(self))));
+        }
+        Node::new("If", children)
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &stmt::While) -> Node {
+        let mut children = vec!(self.expr(&stmt.condition), stmt.body.accept(self));
+        if let Some(increment) = &stmt.increment {
+            children.push(Node::new("Increment", vec!(self.expr(increment))));
+        }
+        Node::new(&label("While", &stmt.label), children)
+    }
+
+    fn visit_func_stmt(&mut self, stmt: &stmt::Func) -> Node {
+        let label = format!("Fn {} {}", stmt.token.lexeme, signature(stmt));
+        self.block(&label, &stmt.body)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &stmt::Return) -> Node {
+        let children = stmt.value.iter().map(|expr| self.expr(expr)).collect();
+        Node::new("Return", children)
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &stmt::Class) -> Node {
+        let label = match &stmt.superclass {
+            Some(Expr::Var(superclass)) => {
+                format!("Class {} < {}", stmt.token.lexeme, superclass.token.lexeme)
+            },
+            _ => format!("Class {}", stmt.token.lexeme),
+        };
+        self.block(&label, &stmt.methods)
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &stmt::Break) -> Node {
+        Node::leaf(&label("Break", &stmt.label))
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &stmt::Continue) -> Node {
+        Node::leaf(&label("Continue", &stmt.label))
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &stmt::Import) -> Node {
+        let label = match &stmt.alias {
+            Some(alias) => format!("Import {} as {}", stmt.path.lexeme, alias.lexeme),
+            None => format!("Import {} {}", stmt.path.lexeme, names(&stmt.names)),
+        };
+        Node::leaf(&label)
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &stmt::Export) -> Node {
+        Node::new("Export", vec!(stmt.declaration.accept(self)))
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &stmt::Throw) -> Node {
+        Node::new("Throw", vec!(self.expr(&stmt.value)))
+    }
+
+    fn visit_try_stmt(&mut self, stmt: &stmt::Try) -> Node {
+        let mut children = vec!(self.block("Body", &stmt.body));
+        if let Some(catch) = &stmt.catch {
+            children.push(self.block(&format!("Catch {}", catch.name.lexeme), &catch.body));
+        }
+        if let Some(finally) = &stmt.finally {
+            children.push(self.block("Finally", finally));
+        }
+        Node::new("Try", children)
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt;
 
 use crate::{
     ast::expr::Expr,
-    token::Token,
+    token::{Token, Span},
 };
 
 pub trait AcceptStmtVisitor {
@@ -22,6 +22,12 @@ pub trait StmtVisitor {
     fn visit_func_stmt(&mut self, stmt: &Func) -> Self::Output;
     fn visit_return_stmt(&mut self, stmt: &Return) -> Self::Output;
     fn visit_class_stmt(&mut self, stmt: &Class) -> Self::Output;
+    fn visit_break_stmt(&mut self, stmt: &Break) -> Self::Output;
+    fn visit_continue_stmt(&mut self, stmt: &Continue) -> Self::Output;
+    fn visit_import_stmt(&mut self, stmt: &Import) -> Self::Output;
+    fn visit_export_stmt(&mut self, stmt: &Export) -> Self::Output;
+    fn visit_throw_stmt(&mut self, stmt: &Throw) -> Self::Output;
+    fn visit_try_stmt(&mut self, stmt: &Try) -> Self::Output;
 }
 
 #[derive(Clone, Debug)]
@@ -35,6 +41,12 @@ pub enum Stmt {
     Func(Func),
     Return(Return),
     Class(Class),
+    Break(Break),
+    Continue(Continue),
+    Import(Import),
+    Export(Export),
+    Throw(Throw),
+    Try(Try),
 }
 
 impl AcceptStmtVisitor for Stmt {
@@ -49,6 +61,12 @@ impl AcceptStmtVisitor for Stmt {
             Stmt::Func(stmt) => visitor.visit_func_stmt(stmt),
             Stmt::Return(stmt) => visitor.visit_return_stmt(stmt),
             Stmt::Class(stmt) => visitor.visit_class_stmt(stmt),
+            Stmt::Break(stmt) => visitor.visit_break_stmt(stmt),
+            Stmt::Continue(stmt) => visitor.visit_continue_stmt(stmt),
+            Stmt::Import(stmt) => visitor.visit_import_stmt(stmt),
+            Stmt::Export(stmt) => visitor.visit_export_stmt(stmt),
+            Stmt::Throw(stmt) => visitor.visit_throw_stmt(stmt),
+            Stmt::Try(stmt) => visitor.visit_try_stmt(stmt),
         }
     }
 }
@@ -58,39 +76,44 @@ impl Stmt {
         Self::StmtExpr(StmtExpr{ expr })
     }
 
-    pub fn new_print(expr: Expr) -> Self {
-        Self::Print(Print{ expr })
+    pub fn new_print(expr: Expr, span: Span) -> Self {
+        Self::Print(Print{ expr, span })
     }
 
-    pub fn new_let(token: Rc<Token>, initializer: Option<Expr>
-        ) -> Self {
-        Self::Let(Let{ token, initializer })
+    pub fn new_let(token: Rc<Token>, annotation: Option<Rc<Token>>,
+        initializer: Option<Expr>) -> Self {
+        Self::Let(Let{ token, annotation, initializer })
     }
 
-    pub fn new_block(stmts: Vec<Stmt>) -> Self {
-        Self::Block(Block{ stmts })
+    pub fn new_block(stmts: Vec<Stmt>, span: Span) -> Self {
+        Self::Block(Block{ stmts, span })
     }
 
     pub fn new_if(condition: Expr, body: Stmt,
-        else_body: Option<Stmt>) -> Self {
-        let else_body = else_body.map(|s| Box::new(s));
+        else_body: Option<Stmt>, span: Span) -> Self {
+        let else_body = else_body.map(Box::new);
         Self::If(If{
             condition,
             body: Box::new(body),
-            else_body
+            else_body,
+            span,
         })
     }
 
-    pub fn new_while(condition: Expr, body: Stmt) -> Self {
+    pub fn new_while(condition: Expr, body: Stmt, increment: Option<Expr>,
+        label: Option<Rc<Token>>, span: Span) -> Self {
         Self::While(While{
             condition,
             body: Box::new(body),
+            increment,
+            label,
+            span,
         })
     }
 
     pub fn new_func(token: Rc<Token>, params: Vec<Rc<Token>>,
-        body: Vec<Stmt>) -> Self {
-        Self::Func(Func { token, params, body })
+        body: Vec<Stmt>, annotations: Annotations) -> Self {
+        Self::Func(Func { token, params, body, annotations })
     }
 
     pub fn new_return(keyword: Rc<Token>, value: Option<Expr>) -> Self {
@@ -101,6 +124,53 @@ impl Stmt {
         superclass: Option<Expr>) -> Self {
         Self::Class(Class { token , methods, superclass })
     }
+
+    pub fn new_break(keyword: Rc<Token>, label: Option<Rc<Token>>) -> Self {
+        Self::Break(Break { keyword, label })
+    }
+
+    pub fn new_continue(keyword: Rc<Token>, label: Option<Rc<Token>>) -> Self {
+        Self::Continue(Continue { keyword, label })
+    }
+
+    pub fn new_import(keyword: Rc<Token>, path: Rc<Token>, alias: Option<Rc<Token>>,
+        names: Vec<Rc<Token>>) -> Self {
+        Self::Import(Import { keyword, path, alias, names })
+    }
+
+    pub fn new_export(keyword: Rc<Token>, declaration: Stmt) -> Self {
+        Self::Export(Export { keyword, declaration: Box::new(declaration) })
+    }
+
+    pub fn new_throw(keyword: Rc<Token>, value: Expr) -> Self {
+        Self::Throw(Throw { keyword, value })
+    }
+
+    pub fn new_try(keyword: Rc<Token>, body: Vec<Stmt>, catch: Option<Catch>,
+        finally: Option<Vec<Stmt>>) -> Self {
+        Self::Try(Try { keyword, body, catch, finally })
+    }
+
+    //statements without a keyword token of their own record their span
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::StmtExpr(stmt) => stmt.expr.span(),
+            Stmt::Print(stmt) => stmt.span.clone(),
+            Stmt::Let(stmt) => stmt.token.span.clone(),
+            Stmt::Block(stmt) => stmt.span.clone(),
+            Stmt::If(stmt) => stmt.span.clone(),
+            Stmt::While(stmt) => stmt.span.clone(),
+            Stmt::Func(stmt) => stmt.token.span.clone(),
+            Stmt::Return(stmt) => stmt.keyword.span.clone(),
+            Stmt::Class(stmt) => stmt.token.span.clone(),
+            Stmt::Break(stmt) => stmt.keyword.span.clone(),
+            Stmt::Continue(stmt) => stmt.keyword.span.clone(),
+            Stmt::Import(stmt) => stmt.keyword.span.clone(),
+            Stmt::Export(stmt) => stmt.keyword.span.clone(),
+            Stmt::Throw(stmt) => stmt.keyword.span.clone(),
+            Stmt::Try(stmt) => stmt.keyword.span.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -110,18 +180,21 @@ pub struct StmtExpr {
 
 #[derive(Clone, Debug)]
 pub struct Print {
-    pub expr: Expr
+    pub expr: Expr,
+    pub span: Span,
 }
 
 #[derive(Clone, Debug)]
 pub struct Let {
     pub token: Rc<Token>,
+    pub annotation: Option<Rc<Token>>, //the type name in `let x: Num`
     pub initializer: Option<Expr>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Block {
-    pub stmts: Vec<Stmt>
+    pub stmts: Vec<Stmt>,
+    pub span: Span,
 }
 
 #[derive(Clone, Debug)]
@@ -129,12 +202,18 @@ pub struct If {
     pub condition: Expr,
     pub body: Box<Stmt>,
     pub else_body: Option<Box<Stmt>>,
+    pub span: Span,
 }
 
+//for loops are desugared into a while with an increment, which still runs
+//when the body is cut short by continue
 #[derive(Clone, Debug)]
 pub struct While {
     pub condition: Expr,
     pub body: Box<Stmt>,
+    pub increment: Option<Expr>,
+    pub label: Option<Rc<Token>>,
+    pub span: Span,
 }
 
 #[derive(Clone, Debug)]
@@ -143,11 +222,69 @@ pub struct Return {
     pub value: Option<Expr>,
 }
 
+#[derive(Clone, Debug)]
+pub struct Break {
+    pub keyword: Rc<Token>,
+    pub label: Option<Rc<Token>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Continue {
+    pub keyword: Rc<Token>,
+    pub label: Option<Rc<Token>>,
+}
+
+//`import "m.lax" as m;` binds the module to alias, while
+//`from "m.lax" import a, b;` binds each of the names it exports
+#[derive(Clone, Debug)]
+pub struct Import {
+    pub keyword: Rc<Token>,
+    pub path: Rc<Token>,
+    pub alias: Option<Rc<Token>>,
+    pub names: Vec<Rc<Token>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Export {
+    pub keyword: Rc<Token>,
+    pub declaration: Box<Stmt>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Throw {
+    pub keyword: Rc<Token>,
+    pub value: Expr,
+}
+
+//a try has a catch, a finally, or both
+#[derive(Clone, Debug)]
+pub struct Try {
+    pub keyword: Rc<Token>,
+    pub body: Vec<Stmt>,
+    pub catch: Option<Catch>,
+    pub finally: Option<Vec<Stmt>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Catch {
+    pub name: Rc<Token>,
+    pub body: Vec<Stmt>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Func {
     pub token: Rc<Token>,
     pub params: Vec<Rc<Token>>,
     pub body: Vec<Stmt>,
+    pub annotations: Annotations,
+}
+
+//the type names in `fn f(a: Str, b) -> Bool`, one entry per parameter.
+//only the checker reads them, at runtime every function is dynamic
+#[derive(Clone, Debug, Default)]
+pub struct Annotations {
+    pub params: Vec<Option<Rc<Token>>>,
+    pub returns: Option<Rc<Token>>,
 }
 
 #[derive(Clone, Debug)]
@@ -159,7 +296,7 @@ pub struct Class {
 
 impl fmt::Display for Func {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.token)
+        write!(f, "<fn {}>", self.token.lexeme)
     }
 }
 
@@ -24,7 +24,48 @@ pub trait Call {
     fn call(&self, interpreter: &mut Interpreter, args: Vec<Arc<RwLock<Value>>>
         ) -> Result<Arc<RwLock<Value>>, RuntimeError>;
 
-    fn arity(&self) -> usize;
+    fn arity(&self) -> Arity;
+}
+
+//number of arguments a callable accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Fixed(usize),
+    Range(usize, usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, num_args: usize) -> bool {
+        match *self {
+            Arity::Fixed(n) => num_args == n,
+            Arity::Range(min, max) => (min..=max).contains(&num_args),
+            Arity::AtLeast(min) => num_args >= min,
+        }
+    }
+
+    //the error for a call with the wrong number of arguments
+    pub fn mismatch(&self, num_args: usize) -> String {
+        let plural = match *self {
+            Arity::Fixed(n) | Arity::AtLeast(n) => n != 1,
+            Arity::Range(..) => true,
+        };
+        let noun = match plural {
+            true => "arguments",
+            false => "argument",
+        };
+        format!("Expected {} {} but got {}.", self, noun, num_args)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arity::Fixed(n) => write!(f, "{}", n),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+            Arity::AtLeast(min) => write!(f, "at least {}", min),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -35,7 +76,7 @@ pub enum FuncType {
     None,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Callable {
     NativeFn(NativeFn),
     LaxFn(LaxFn),
@@ -43,7 +84,7 @@ pub enum Callable {
 }
 
 impl Callable {
-    pub fn new_native_fn(name: String, func: NativeFnType, arity: usize) -> Self {
+    pub fn new_native_fn(name: String, func: NativeFnType, arity: Arity) -> Self {
         Callable::NativeFn(NativeFn { name, func, arity })
     }
 
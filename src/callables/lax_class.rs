@@ -14,6 +14,7 @@ use crate::{
 use super::{
     lax_object::LaxObject,
     lax_functions::LaxFn,
+    callable::Arity,
     Callable,
     Call,
 };
@@ -26,6 +27,16 @@ pub struct LaxClass {
 }
 
 impl LaxClass {
+    pub fn method_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.methods.keys().cloned().collect();
+        if let Some(value) = &self.superclass {
+            if let Value::Callable(Callable::LaxClass(superclass)) = &*value.read().unwrap() {
+                names.extend(superclass.method_names());
+            }
+        }
+        names
+    }
+
     pub fn find_method(&self, name: &str) -> Option<Arc<RwLock<Value>>> {
         let method = self.methods
             .get(name)
@@ -60,14 +71,14 @@ impl Call for LaxClass {
         Ok(value)
     }
 
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         let value = match self.find_method("init") {
             Some(initializer) => initializer,
-            None => return 0,
+            None => return Arity::Fixed(0),
         };
         let arity = match &*value.read().unwrap() {
             Value::Callable(func) => func.arity(),
-            _ => 0,
+            _ => Arity::Fixed(0),
         };
         arity
     }
@@ -82,7 +93,7 @@ impl fmt::Display for LaxClass {
 impl fmt::Debug for LaxClass {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "{}:", self.name)?;
-        for (k, _) in &self.methods {
+        for k in self.methods.keys() {
             writeln!(f, "{}", k)?;
         }
         Ok(())
@@ -3,7 +3,7 @@ use std::sync::{Arc, Mutex, RwLock};
 use std::fmt;
 
 use crate::{
-    interpreter::Interpreter,
+    interpreter::{Interpreter, Flow},
     environment::Environment,
     error::RuntimeError,
     ast::stmt::Func,
@@ -14,7 +14,7 @@ use crate::{
     },
 };
 
-use super::Call;
+use super::{Call, callable::Arity};
 
 #[derive(Clone, Debug)]
 pub struct LaxFn {
@@ -49,7 +49,8 @@ impl Call for LaxFn {
 
         let output = interpreter.execute_block(&self.declaration.body, env)?;
 
-        if let Some(val) = output {
+        //a bare return in an initializer still hands back the instance
+        if let (Flow::Return(val), false) = (output, self.is_init) {
             return Ok(val)
         }
         if self.is_init {
@@ -57,7 +58,7 @@ impl Call for LaxFn {
                 TokenType::Fn,
                 Rc::new("this".to_string()),
                 Arc::new(RwLock::new(Value::None)),
-                0
+                self.declaration.token.span.clone()
             );
             return self.closure.lock().unwrap().get_at(0, Rc::new(sudo_token))
         }
@@ -65,8 +66,8 @@ impl Call for LaxFn {
         Ok(Arc::new(RwLock::new(Value::None)))
     }
 
-    fn arity(&self) -> usize {
-        self.declaration.params.len()
+    fn arity(&self) -> Arity {
+        Arity::Fixed(self.declaration.params.len())
     }
 }
 
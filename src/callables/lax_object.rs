@@ -29,7 +29,7 @@ impl LaxObject {
     pub fn get(&self, token: Rc<Token>, object: Arc<RwLock<Value>>
         ) -> Result<Arc<RwLock<Value>>, RuntimeError> {
         if let Some(field) = self.fields.get(token.lexeme.as_str()) {
-            return Ok(Arc::clone(&field))
+            return Ok(Arc::clone(field))
         }
         if let Some(binding) = self.class.find_method(token.lexeme.as_str()) {
             let value = binding.write().unwrap();
@@ -43,6 +43,17 @@ impl LaxObject {
         Err(RuntimeError::new(token, &message))
     }
 
+    pub fn field(&self, name: &str) -> Option<Value> {
+        self.fields.get(name).map(|field| field.read().unwrap().clone())
+    }
+
+    //fields first, then methods, including inherited ones
+    pub fn members(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.fields.keys().cloned().collect();
+        names.extend(self.class.method_names());
+        names
+    }
+
     pub fn set(&mut self, token: Rc<Token>, value: Arc<RwLock<Value>>) {
         self.fields.insert(token.lexeme.to_string(), value);
     }
@@ -6,3 +6,4 @@ pub mod lax_object;
 
 pub use callable::Callable;
 pub use callable::Call;
+pub use callable::Arity;
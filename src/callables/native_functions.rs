@@ -1,33 +1,36 @@
 use std::{
-    sync::{Arc, Mutex, RwLock},
+    collections::BTreeMap,
+    rc::Rc,
+    sync::{Arc, RwLock},
     time::SystemTime,
     fmt,
 };
 
 use crate::{
     interpreter::Interpreter,
-    callables::{Callable, Call},
-    environment::Environment,
+    callables::{Call, Arity},
     error::RuntimeError,
-    token::Value,
+    token::{Value, MapKey, list_position},
 };
 
-pub type NativeFnType = Box<dyn Fn(&Interpreter, Vec<Arc<RwLock<Value>>>) -> Value>;
+pub type NativeFnType = Rc<dyn Fn(&mut Interpreter, Vec<Arc<RwLock<Value>>>
+    ) -> Result<Value, RuntimeError>>;
 
+#[derive(Clone)]
 pub struct NativeFn {
     pub func: NativeFnType,
-    pub arity: usize,
+    pub arity: Arity,
     pub name: String,
 }
 
 impl Call for NativeFn {
     fn call(&self, interpreter: &mut Interpreter, args: Vec<Arc<RwLock<Value>>>
         ) -> Result<Arc<RwLock<Value>>, RuntimeError> {
-        let value = (self.func)(interpreter, args);
+        let value = (self.func)(interpreter, args)?;
         Ok(Arc::new(RwLock::new(value)))
     }
 
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         self.arity
     }
 }
@@ -38,32 +41,137 @@ impl fmt::Display for NativeFn {
     }
 }
 
-pub struct NativeDeclarations {
-    globals: Arc<Mutex<Environment>>
+pub struct NativeDeclarations<'a> {
+    interpreter: &'a mut Interpreter,
 }
 
-impl NativeDeclarations {
-    pub fn new(globals: Arc<Mutex<Environment>>) -> Self {
-        NativeDeclarations { globals }
+impl<'a> NativeDeclarations<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        NativeDeclarations { interpreter }
     }
 
-    pub fn declare_natives(&mut self) -> Arc<Mutex<Environment>> {
+    pub fn declare_natives(&mut self) {
         self.declare_clock();
-        std::mem::replace(&mut self.globals, Environment::new_wrapped(None))
+        self.declare_len();
+        self.declare_push();
+        self.declare_pop();
+        self.declare_insert();
+        self.declare_has();
+        self.declare_remove();
+        self.declare_keys();
+        self.declare_values();
     }
 
     fn declare_clock(&mut self) {
-        let clock_fn = |_: &Interpreter, _: Vec<Arc<RwLock<Value>>>| -> Value {
+        self.interpreter.define_native("clock", Arity::Fixed(0), |_, _| {
             let current_time = SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .expect("Failed to get current time")
                 .as_secs_f64();
-            Value::Num(current_time)
-        };
-        let name = "clock".to_string();
-        let callable = Callable::new_native_fn(name.clone(), Box::new(clock_fn), 0);
-        let value = Arc::new(RwLock::new(Value::Callable(callable)));
-        self.globals.lock().unwrap().define(name, value);
+            Ok(Value::Num(current_time))
+        });
+    }
+
+    fn declare_len(&mut self) {
+        self.interpreter.define_native("len", Arity::Fixed(1), |_, args| {
+            match &*args[0].read().unwrap() {
+                Value::List(list) => Ok(Value::Num(list.read().unwrap().len() as f64)),
+                Value::Map(map) => Ok(Value::Num(map.read().unwrap().len() as f64)),
+                Value::String(string) => Ok(Value::Num(string.chars().count() as f64)),
+                _ => Err(RuntimeError::without_token("len() expects a list, map or string.")),
+            }
+        });
+    }
+
+    fn declare_push(&mut self) {
+        self.interpreter.define_native("push", Arity::Fixed(2), |_, args| {
+            let list = expect_list(&args[0], "push")?;
+            let value = args[1].read().unwrap().clone();
+            list.write().unwrap().push(value);
+            Ok(Value::None)
+        });
+    }
+
+    fn declare_pop(&mut self) {
+        self.interpreter.define_native("pop", Arity::Fixed(1), |_, args| {
+            let list = expect_list(&args[0], "pop")?;
+            let value = list.write().unwrap().pop();
+            value.ok_or_else(|| RuntimeError::without_token("Can't pop from an empty list."))
+        });
+    }
+
+    fn declare_insert(&mut self) {
+        self.interpreter.define_native("insert", Arity::Fixed(3), |_, args| {
+            let list = expect_list(&args[0], "insert")?;
+            let mut list = list.write().unwrap();
+            //inserting at the length appends
+            let position = list_position(&args[1].read().unwrap(), list.len() + 1)
+                .map_err(|message| RuntimeError::without_token(&message))?;
+            list.insert(position, args[2].read().unwrap().clone());
+            Ok(Value::None)
+        });
+    }
+
+    fn declare_has(&mut self) {
+        self.interpreter.define_native("has", Arity::Fixed(2), |_, args| {
+            let map = expect_map(&args[0], "has")?;
+            let key = map_key(&args[1])?;
+            let has = map.read().unwrap().contains_key(&key);
+            Ok(Value::Bool(has))
+        });
+    }
+
+    //returns the removed value, or nil when the key was absent
+    fn declare_remove(&mut self) {
+        self.interpreter.define_native("remove", Arity::Fixed(2), |_, args| {
+            let map = expect_map(&args[0], "remove")?;
+            let key = map_key(&args[1])?;
+            let value = map.write().unwrap().remove(&key);
+            Ok(value.unwrap_or(Value::None))
+        });
+    }
+
+    fn declare_keys(&mut self) {
+        self.interpreter.define_native("keys", Arity::Fixed(1), |_, args| {
+            let map = expect_map(&args[0], "keys")?;
+            let keys = map.read().unwrap().keys().map(MapKey::to_value).collect();
+            Ok(Value::new_list(keys))
+        });
+    }
+
+    fn declare_values(&mut self) {
+        self.interpreter.define_native("values", Arity::Fixed(1), |_, args| {
+            let map = expect_map(&args[0], "values")?;
+            let values = map.read().unwrap().values().cloned().collect();
+            Ok(Value::new_list(values))
+        });
+    }
+}
+
+fn expect_map(arg: &Arc<RwLock<Value>>, name: &str
+    ) -> Result<Arc<RwLock<BTreeMap<MapKey, Value>>>, RuntimeError> {
+    match &*arg.read().unwrap() {
+        Value::Map(map) => Ok(Arc::clone(map)),
+        _ => {
+            let message = format!("{}() expects a map as its first argument.", name);
+            Err(RuntimeError::without_token(&message))
+        },
+    }
+}
+
+fn map_key(arg: &Arc<RwLock<Value>>) -> Result<MapKey, RuntimeError> {
+    MapKey::from_value(&arg.read().unwrap())
+        .map_err(|message| RuntimeError::without_token(&message))
+}
+
+fn expect_list(arg: &Arc<RwLock<Value>>, name: &str
+    ) -> Result<Arc<RwLock<Vec<Value>>>, RuntimeError> {
+    match &*arg.read().unwrap() {
+        Value::List(list) => Ok(Arc::clone(list)),
+        _ => {
+            let message = format!("{}() expects a list as its first argument.", name);
+            Err(RuntimeError::without_token(&message))
+        },
     }
 }
 
@@ -72,4 +180,3 @@ impl fmt::Debug for NativeFn {
         write!(f, "Native Function: {}", self.name)
     }
 }
-
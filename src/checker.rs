@@ -0,0 +1,579 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+use std::mem;
+use std::fmt;
+
+use crate::{
+    ast::{
+        expr::{self, ExprVisitor, Expr, AcceptExprVisitor, Update},
+        stmt::{self, StmtVisitor, Stmt, AcceptStmtVisitor, Func},
+    },
+    token::{Token, TokenType, Value, Span},
+    error::{ErrorStatus, TypeError},
+    vm::object::VmObject,
+};
+
+//the types annotations can name. Any is inferred whenever the checker
+//can't be sure, and every check passes against it
+#[derive(Clone, Debug)]
+pub enum Type {
+    Num,
+    Str,
+    Bool,
+    Nil,
+    List,
+    Map,
+    Fn(Option<Rc<Signature>>), //known when the function's declaration is in view
+    Any,
+}
+
+#[derive(Debug)]
+pub struct Signature {
+    params: Vec<(Rc<Token>, Type)>,
+    returns: Type,
+}
+
+impl Type {
+    fn named(name: &str) -> Option<Type> {
+        Some(match name {
+            "Num" => Type::Num,
+            "Str" => Type::Str,
+            "Bool" => Type::Bool,
+            "Nil" => Type::Nil,
+            "List" => Type::List,
+            "Map" => Type::Map,
+            "Fn" => Type::Fn(None),
+            "Any" => Type::Any,
+            _ => return None,
+        })
+    }
+
+    fn of(value: &Value) -> Type {
+        match value {
+            Value::Num(_) => Type::Num,
+            Value::String(_) => Type::Str,
+            Value::Bool(_) => Type::Bool,
+            Value::None => Type::Nil,
+            Value::List(_) => Type::List,
+            Value::Map(_) => Type::Map,
+            Value::Callable(_) => Type::Fn(None),
+            Value::VmObject(VmObject::Instance(_)) => Type::Any,
+            Value::VmObject(_) => Type::Fn(None),
+            _ => Type::Any,
+        }
+    }
+
+    //a value that behaves like every value of the type under the operators,
+    //so they can be checked by running them. operators treat every value
+    //other than numbers and strings alike, which covers functions
+    fn sample(&self) -> Option<Value> {
+        Some(match self {
+            Type::Num => Value::Num(1.0),
+            Type::Str => Value::String(String::new()),
+            Type::Bool => Value::Bool(true),
+            Type::Nil | Type::Fn(_) => Value::None,
+            Type::List => Value::new_list(vec!()),
+            Type::Map => Value::new_map(BTreeMap::new()),
+            Type::Any => return None,
+        })
+    }
+
+    fn accepts(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Fn(_), Type::Fn(_)) => true,
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+
+    fn is_any(&self) -> bool {
+        matches!(self, Type::Any)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Type::Num => "Num",
+            Type::Str => "Str",
+            Type::Bool => "Bool",
+            Type::Nil => "Nil",
+            Type::List => "List",
+            Type::Map => "Map",
+            Type::Fn(_) => "Fn",
+            Type::Any => "Any",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+//an expression's type, and whether it follows from an annotation. mistakes
+//in code that never mentions an annotation are left for the runtime
+#[derive(Clone)]
+pub struct Inferred {
+    ty: Type,
+    annotated: bool,
+}
+
+impl Inferred {
+    fn dynamic(ty: Type) -> Self {
+        Inferred { ty, annotated: false }
+    }
+
+    fn any() -> Self {
+        Inferred::dynamic(Type::Any)
+    }
+}
+
+struct Binding {
+    ty: Type,
+    declared: bool, //written with an annotation, so assignments must match it
+    annotated: bool,
+}
+
+//runs after the resolver. unannotated variables take the type of their
+//initializer unless something assigns to them later, then they are Any
+pub struct Checker<'a> {
+    scopes: Vec<HashMap<String, Binding>>, //globals first
+    reassigned: HashSet<String>,
+    function: Option<(Rc<Token>, Type)>, //name and return type of the function being checked
+    reporting: bool,
+    status: &'a mut ErrorStatus,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(status: &'a mut ErrorStatus) -> Self {
+        Checker {
+            scopes: vec!(HashMap::new()),
+            reassigned: HashSet::new(),
+            function: None,
+            reporting: true,
+            status,
+        }
+    }
+
+    //the first pass only collects the names that are assigned to, since a
+    //closure can reassign a variable before code above it runs
+    pub fn check(&mut self, stmts: &[Stmt]) {
+        self.reporting = false;
+        self.check_stmts(stmts);
+        self.scopes = vec!(HashMap::new());
+        self.reporting = true;
+        self.check_stmts(stmts);
+    }
+
+    fn check_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            stmt.accept(self);
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) -> Inferred {
+        expr.accept(self)
+    }
+
+    fn error(&mut self, span: Span, message: &str) {
+        if self.reporting {
+            self.status.report_compile_error(TypeError::new(span, message));
+        }
+    }
+
+    fn in_scope<T>(&mut self, check: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let result = check(self);
+        self.scopes.pop();
+        result
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn declare(&mut self, name: &Rc<Token>, declared: Option<Type>, inferred: Inferred) {
+        let binding = match declared {
+            Some(ty) => Binding { ty, declared: true, annotated: true },
+            None if self.reassigned.contains(name.lexeme.as_str()) => {
+                Binding { ty: Type::Any, declared: false, annotated: false }
+            },
+            None => Binding { ty: inferred.ty, declared: false, annotated: inferred.annotated },
+        };
+        self.scopes.last_mut().unwrap().insert(name.lexeme.to_string(), binding);
+    }
+
+    fn annotation(&mut self, name: &Option<Rc<Token>>) -> Option<Type> {
+        let name = name.as_ref()?;
+        match Type::named(&name.lexeme) {
+            Some(ty) => Some(ty),
+            None => {
+                let message = format!("Unknown type '{}'.", name.lexeme);
+                self.error(name.span.clone(), &message);
+                Some(Type::Any)
+            },
+        }
+    }
+
+    fn expect(&mut self, expected: &Type, actual: &Inferred, what: &str, span: Span) {
+        if !expected.accepts(&actual.ty) {
+            let message = format!("Expected {} for {} but got {}.", expected, what, actual.ty);
+            self.error(span, &message);
+        }
+    }
+
+    //runs the operator on sample values, so the checker reports exactly
+    //what the runtime would
+    fn binary(&mut self, left: &Inferred, operator: &Token, right: &Inferred) -> Inferred {
+        let annotated = left.annotated || right.annotated;
+        let ty = match (left.ty.sample(), right.ty.sample()) {
+            (Some(left), Some(right)) => match left.binary(&operator.token_type, &right) {
+                Ok(value) => Type::of(&value),
+                Err(message) => {
+                    if annotated {
+                        self.error(operator.span.clone(), message);
+                    }
+                    return Inferred::any()
+                },
+            },
+            _ if is_comparison(&operator.token_type) => Type::Bool,
+            _ => Type::Any,
+        };
+        Inferred { ty, annotated }
+    }
+
+    fn update(&mut self, current: &Inferred, update: &Option<Update>, value: Inferred) -> Inferred {
+        match update {
+            Some(update) => self.binary(current, &update.operator, &value),
+            None => value,
+        }
+    }
+
+    //the value an update expression produces: `a++` gives the old value
+    fn updated(current: Inferred, update: &Option<Update>, value: Inferred) -> Inferred {
+        match update {
+            Some(update) if update.postfix => current,
+            _ => value,
+        }
+    }
+
+    fn signature(&mut self, func: &Func) -> Rc<Signature> {
+        let params = func.params.iter().zip(&func.annotations.params)
+            .map(|(param, annotation)| {
+                (Rc::clone(param), self.annotation(annotation).unwrap_or(Type::Any))
+            })
+            .collect();
+        let returns = self.annotation(&func.annotations.returns).unwrap_or(Type::Any);
+        Rc::new(Signature { params, returns })
+    }
+
+    fn check_func(&mut self, func: &Func, signature: &Signature) {
+        let function = (Rc::clone(&func.token), signature.returns.clone());
+        let enclosing = self.function.replace(function);
+        self.in_scope(|checker| {
+            for (param, ty) in &signature.params {
+                let declared = Some(ty.clone()).filter(|ty| !ty.is_any());
+                checker.declare(param, declared, Inferred::any());
+            }
+            checker.check_stmts(&func.body);
+        });
+        self.function = enclosing;
+    }
+
+    //reports property access, indexing and calls on values that can't
+    //support them, but only when an annotation says what the value is
+    fn unsupported(&mut self, object: &Inferred, supported: bool, span: Span, message: &str) {
+        if object.annotated && !object.ty.is_any() && !supported {
+            self.error(span, message);
+        }
+    }
+}
+
+//comparisons give a Bool whenever they succeed, even on values of unknown type
+fn is_comparison(operator: &TokenType) -> bool {
+    matches!(operator, TokenType::EqualEqual | TokenType::BangEqual
+        | TokenType::Greater | TokenType::GreaterEqual
+        | TokenType::Less | TokenType::LessEqual)
+}
+
+impl StmtVisitor for Checker<'_> {
+    type Output = ();
+
+    fn visit_expr_stmt(&mut self, stmt: &stmt::StmtExpr) {
+        self.check_expr(&stmt.expr);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &stmt::Print) {
+        self.check_expr(&stmt.expr);
+    }
+
+    //`let x: Num;` is allowed, the variable just holds nil until assigned
+    fn visit_let_stmt(&mut self, stmt: &stmt::Let) {
+        let declared = self.annotation(&stmt.annotation);
+        let inferred = match &stmt.initializer {
+            Some(initializer) => {
+                let inferred = self.check_expr(initializer);
+                if let Some(declared) = &declared {
+                    let what = format!("'{}'", stmt.token.lexeme);
+                    self.expect(declared, &inferred, &what, initializer.span());
+                }
+                inferred
+            },
+            None => Inferred::dynamic(Type::Nil),
+        };
+        self.declare(&stmt.token, declared, inferred);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &stmt::Block) {
+        self.in_scope(|checker| checker.check_stmts(&stmt.stmts));
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &stmt::If) {
+        self.check_expr(&stmt.condition);
+        stmt.body.accept(self);
+        if let Some(else_body) = &stmt.else_body {
+            else_body.accept(self);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &stmt::While) {
+        self.check_expr(&stmt.condition);
+        stmt.body.accept(self);
+        if let Some(increment) = &stmt.increment {
+            self.check_expr(increment);
+        }
+    }
+
+    //declared before its body is checked so recursive calls are checked too
+    fn visit_func_stmt(&mut self, stmt: &stmt::Func) {
+        let signature = self.signature(stmt);
+        let ty = Inferred::dynamic(Type::Fn(Some(Rc::clone(&signature))));
+        self.declare(&stmt.token, None, ty);
+        self.check_func(stmt, &signature);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &stmt::Return) {
+        let inferred = match &stmt.value {
+            Some(value) => self.check_expr(value),
+            None => Inferred::dynamic(Type::Nil),
+        };
+        if let Some((name, returns)) = self.function.clone() {
+            let span = match &stmt.value {
+                Some(value) => value.span(),
+                None => stmt.keyword.span.clone(),
+            };
+            let what = format!("the return value of '{}'", name.lexeme);
+            self.expect(&returns, &inferred, &what, span);
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &stmt::Class) {
+        self.declare(&stmt.token, None, Inferred::dynamic(Type::Fn(None)));
+        if let Some(superclass) = &stmt.superclass {
+            self.check_expr(superclass);
+        }
+        for method in &stmt.methods {
+            if let Stmt::Func(method) = method {
+                let signature = self.signature(method);
+                self.check_func(method, &signature);
+            }
+        }
+    }
+
+    fn visit_break_stmt(&mut self, _stmt: &stmt::Break) {}
+
+    fn visit_continue_stmt(&mut self, _stmt: &stmt::Continue) {}
+
+    fn visit_import_stmt(&mut self, stmt: &stmt::Import) {
+        for name in stmt.alias.iter().chain(&stmt.names) {
+            self.declare(name, None, Inferred::any());
+        }
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &stmt::Export) {
+        stmt.declaration.accept(self);
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &stmt::Throw) {
+        self.check_expr(&stmt.value);
+    }
+
+    fn visit_try_stmt(&mut self, stmt: &stmt::Try) {
+        self.in_scope(|checker| checker.check_stmts(&stmt.body));
+        if let Some(catch) = &stmt.catch {
+            self.in_scope(|checker| {
+                checker.declare(&catch.name, None, Inferred::any());
+                checker.check_stmts(&catch.body);
+            });
+        }
+        if let Some(finally) = &stmt.finally {
+            self.in_scope(|checker| checker.check_stmts(finally));
+        }
+    }
+}
+
+impl ExprVisitor for Checker<'_> {
+    type Output = Inferred;
+
+    fn visit_binary_expr(&mut self, expr: &expr::Binary) -> Inferred {
+        let left = self.check_expr(&expr.left);
+        let right = self.check_expr(&expr.right);
+        self.binary(&left, &expr.operator, &right)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &expr::Grouping) -> Inferred {
+        self.check_expr(&expr.expr)
+    }
+
+    fn visit_literal_expr(&mut self, expr: &expr::Literal) -> Inferred {
+        Inferred::dynamic(Type::of(&expr.value.read().unwrap()))
+    }
+
+    fn visit_unary_expr(&mut self, expr: &expr::Unary) -> Inferred {
+        let right = self.check_expr(&expr.right);
+        let operator = &expr.operator.token_type;
+        let ty = match right.ty.sample() {
+            Some(value) => match value.unary(operator) {
+                Ok(value) => Type::of(&value),
+                Err(message) => {
+                    if right.annotated {
+                        self.error(expr.operator.span.clone(), message);
+                    }
+                    return Inferred::any()
+                },
+            },
+            None => match operator {
+                TokenType::Bang => Type::Bool,
+                TokenType::Minus => Type::Num,
+                _ => Type::Any,
+            },
+        };
+        Inferred { ty, annotated: right.annotated }
+    }
+
+    fn visit_var_expr(&mut self, expr: &expr::Var) -> Inferred {
+        match self.lookup(&expr.token.lexeme) {
+            Some(binding) => Inferred { ty: binding.ty.clone(), annotated: binding.annotated },
+            None => Inferred::any(),
+        }
+    }
+
+    fn visit_assign_expr(&mut self, expr: &expr::Assign) -> Inferred {
+        self.reassigned.insert(expr.token.lexeme.to_string());
+        let value = self.check_expr(&expr.value);
+        let (current, declared) = match self.lookup(&expr.token.lexeme) {
+            Some(binding) => {
+                let current = Inferred { ty: binding.ty.clone(), annotated: binding.annotated };
+                (current, binding.declared)
+            },
+            None => (Inferred::any(), false),
+        };
+        let value = self.update(&current, &expr.update, value);
+        if declared {
+            let what = format!("'{}'", expr.token.lexeme);
+            self.expect(&current.ty, &value, &what, expr.value.span());
+        }
+        Self::updated(current, &expr.update, value)
+    }
+
+    fn visit_logical_expr(&mut self, expr: &expr::Logical) -> Inferred {
+        let left = self.check_expr(&expr.left);
+        let right = self.check_expr(&expr.right);
+        let annotated = left.annotated || right.annotated;
+        match mem::discriminant(&left.ty) == mem::discriminant(&right.ty) {
+            true => Inferred { ty: left.ty, annotated },
+            false => Inferred::any(),
+        }
+    }
+
+    fn visit_call_expr(&mut self, expr: &expr::Call) -> Inferred {
+        let callee = self.check_expr(&expr.callee);
+        let args: Vec<Inferred> = expr.args.iter().map(|arg| self.check_expr(arg)).collect();
+        let supported = matches!(callee.ty, Type::Fn(_));
+        let message = "Can only call functions and classes.";
+        self.unsupported(&callee, supported, expr.paren.span.clone(), message);
+
+        let signature = match &callee.ty {
+            Type::Fn(Some(signature)) if signature.params.len() == args.len() => signature,
+            _ => return Inferred::any(),
+        };
+        for ((param, ty), (arg, inferred)) in signature.params.iter().zip(expr.args.iter().zip(&args)) {
+            let what = format!("parameter '{}'", param.lexeme);
+            self.expect(ty, inferred, &what, arg.span());
+        }
+        let returns = signature.returns.clone();
+        let annotated = !returns.is_any();
+        Inferred { ty: returns, annotated }
+    }
+
+    fn visit_get_expr(&mut self, expr: &expr::Get) -> Inferred {
+        let object = self.check_expr(&expr.object);
+        self.unsupported(&object, false, expr.token.span.clone(), "Only instances have properties.");
+        Inferred::any()
+    }
+
+    fn visit_set_expr(&mut self, expr: &expr::Set) -> Inferred {
+        let object = self.check_expr(&expr.object);
+        let value = self.check_expr(&expr.value);
+        self.unsupported(&object, false, expr.token.span.clone(), "Only instances have fields.");
+        match expr.update {
+            Some(_) => Inferred::any(),
+            None => value,
+        }
+    }
+
+    fn visit_this_expr(&mut self, _expr: &expr::This) -> Inferred {
+        Inferred::any()
+    }
+
+    fn visit_super_expr(&mut self, _expr: &expr::Super) -> Inferred {
+        Inferred::any()
+    }
+
+    fn visit_list_expr(&mut self, expr: &expr::List) -> Inferred {
+        for element in &expr.elements {
+            self.check_expr(element);
+        }
+        Inferred::dynamic(Type::List)
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &expr::Interpolation) -> Inferred {
+        for part in &expr.parts {
+            self.check_expr(part);
+        }
+        Inferred::dynamic(Type::Str)
+    }
+
+    fn visit_map_expr(&mut self, expr: &expr::Map) -> Inferred {
+        for (key, value) in &expr.entries {
+            self.check_expr(key);
+            self.check_expr(value);
+        }
+        Inferred::dynamic(Type::Map)
+    }
+
+    fn visit_index_expr(&mut self, expr: &expr::Index) -> Inferred {
+        let object = self.check_expr(&expr.object);
+        self.check_expr(&expr.index);
+        let supported = matches!(object.ty, Type::List | Type::Map);
+        let message = "Only lists and maps can be indexed.";
+        self.unsupported(&object, supported, expr.bracket.span.clone(), message);
+        Inferred::any()
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &expr::SetIndex) -> Inferred {
+        let object = self.check_expr(&expr.object);
+        self.check_expr(&expr.index);
+        let value = self.check_expr(&expr.value);
+        let supported = matches!(object.ty, Type::List | Type::Map);
+        let message = "Only lists and maps can be indexed.";
+        self.unsupported(&object, supported, expr.bracket.span.clone(), message);
+        match expr.update {
+            Some(_) => Inferred::any(),
+            None => value,
+        }
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &expr::Lambda) -> Inferred {
+        let signature = self.signature(&expr.func);
+        self.check_func(&expr.func, &signature);
+        Inferred::dynamic(Type::Fn(Some(signature)))
+    }
+}
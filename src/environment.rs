@@ -50,9 +50,10 @@ impl Environment {
     pub fn get_at(&self, distance: usize, token: Rc<Token>
         ) -> Result<Arc<RwLock<Value>> , RuntimeError> {
         let value = match self.ancestor(distance) {
+            _ if distance == 0 => self.values.get(token.lexeme.as_str()).cloned(),
             Some(env) => { 
                 env.lock().unwrap().values
-                   .get_mut(token.lexeme.as_str()).cloned()
+                   .get(token.lexeme.as_str()).cloned()
             },
             None => { 
                 let message = format!("The scope where '{}' is declared cannot be accessed", token.lexeme);
@@ -90,6 +91,9 @@ impl Environment {
     pub fn assign_at(&mut self, distance: usize, token: Rc<Token>, value: Arc<RwLock<Value>>
         ) -> Result<(), RuntimeError> {
         match self.ancestor(distance) {
+            _ if distance == 0 => {
+                self.values.insert(token.lexeme.to_string(), value);
+            },
             Some(env) => { 
                 env.lock()
                    .unwrap()
@@ -104,14 +108,25 @@ impl Environment {
         Ok(())
     }
 
+    //the outermost environment, which holds the globals of the module a
+    //function was declared in
+    pub fn root(env: &Arc<Mutex<Environment>>) -> Arc<Mutex<Environment>> {
+        let mut root = Arc::clone(env);
+        loop {
+            let enclosing = root.lock().unwrap().enclosing.clone();
+            match enclosing {
+                Some(env) => root = env,
+                None => return root,
+            }
+        }
+    }
+
+    //distance zero is this environment, which the caller already holds
     fn ancestor(&self, distance: usize) -> Option<Arc<Mutex<Environment>>> {
-        let mut env = Arc::new(Mutex::new(self.clone()));
-        for _ in 0..distance {
+        let mut env = self.enclosing.clone()?;
+        for _ in 1..distance {
             let enclosing = env.lock().unwrap().enclosing.clone();
-            env = match enclosing {
-                Some(env) => env,
-                None => return None
-            };
+            env = enclosing?;
         }
         Some(env)
     }
@@ -124,15 +139,13 @@ impl fmt::Debug for Environment {
         for (k, v) in &self.values {
             writeln!(f, "{}: {}", k, v.read().unwrap())?;
         }
-        loop {
-            let enclosing = match &self.enclosing {
-                Some(env) => env,
-                None => break,
-            };
-
-            for (k, v) in &enclosing.lock().unwrap().values {
+        let mut enclosing = self.enclosing.clone();
+        while let Some(env) = enclosing {
+            let env = env.lock().unwrap();
+            for (k, v) in &env.values {
                 writeln!(f, "{}: {}", k, v.read().unwrap())?;
             }
+            enclosing = env.enclosing.clone();
         }
         Ok(())
     }
@@ -1,72 +1,580 @@
 use std::{
+    collections::HashMap,
     error::Error,
     fmt,
+    io::{self, Write},
     rc::Rc,
+    str::FromStr,
+    sync::Arc,
 };
 
-use crate::token::{Token, TokenType};
+use crate::{
+    json,
+    token::{Token, TokenType, Span, Value},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("Unknown error format '{}'.", name)),
+        }
+    }
+}
 
 pub struct ErrorStatus {
     pub had_compile_error: bool,
     pub had_runtime_error: bool,
+    pub warning_count: usize,
+    output: Box<dyn Write>,
+    format: ErrorFormat,
+    sources: HashMap<String, String>,
+    runs: HashMap<String, usize>, //how many sources were added under each name
 }
 
 impl ErrorStatus {
     pub fn new() -> Self {
+        ErrorStatus::with_output(Box::new(io::stderr()))
+    }
+
+    pub fn with_output(output: Box<dyn Write>) -> Self {
         ErrorStatus { 
             had_compile_error: false,
             had_runtime_error: false,
+            warning_count: 0,
+            output,
+            format: ErrorFormat::Human,
+            sources: HashMap::new(),
+            runs: HashMap::new(),
         }
     }
 
-    pub fn report_compile_error<E: Error>(&mut self, error: E) {
-        eprintln!("{}", error);
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    pub fn set_format(&mut self, format: ErrorFormat) {
+        self.format = format;
+    }
+
+    //source text is kept so diagnostics can quote the offending line. every
+    //source gets a name of its own, so the second `<repl>` entry is
+    //`<repl#2>` and a function declared by the first still quotes the first
+    pub fn add_source(&mut self, file: &str, source: &str) -> Rc<String> {
+        let runs = self.runs.entry(file.to_string()).or_insert(0);
+        *runs += 1;
+        let name = match (*runs, file.strip_suffix('>')) {
+            (1, _) => file.to_string(),
+            (n, Some(file)) => format!("{}#{}>", file, n),
+            (n, None) => format!("{}#{}", file, n),
+        };
+        self.sources.insert(name.clone(), source.to_string());
+        Rc::new(name)
+    }
+
+    //for messages that aren't diagnostics, like the REPL's
+    pub fn report_message(&mut self, message: &str) {
+        let _ = writeln!(self.output, "{}", message);
+    }
+
+    pub fn report_compile_error<E: Diagnostic>(&mut self, error: E) {
+        self.write("error", error);
         self.had_compile_error = true;
     }
 
-    pub fn report_runtime_error<E: Error>(&mut self, error: E) {
-        eprintln!("{}", error);
+    pub fn report_runtime_error<E: Diagnostic>(&mut self, error: E) {
+        self.write("error", error);
         self.had_runtime_error = true;
     }
+
+    pub fn report_io_error(&mut self, error: IoError) {
+        self.write("error", error);
+    }
+
+    //warnings share the output but never stop a program from running
+    pub fn report_warning<E: Diagnostic>(&mut self, warning: E) {
+        self.write("warning", warning);
+        self.warning_count += 1;
+    }
+
+    //a diagnostic that cannot be written has nowhere left to be reported
+    fn write<E: Diagnostic>(&mut self, severity: &str, error: E) {
+        let rendered = match self.format {
+            ErrorFormat::Human => self.render(severity, &error),
+            ErrorFormat::Json => self.render_json(severity, &error),
+        };
+        let _ = write!(self.output, "{}", rendered);
+        let _ = self.output.flush();
+    }
+
+    //one object per line so tools can stream diagnostics as they arrive:
+    //  {"severity":"error","phase":"runtime","code":"runtime-error","message":"...",
+    //   "file":"a.lax","span":{"start":6,"end":9,"line":1,"column":7},"notes":[]}
+    //errors raised inside a function also carry
+    //  "trace":[{"function":"f","file":"a.lax","line":2}, ...]
+    pub fn render_json<E: Diagnostic>(&self, severity: &str, error: &E) -> String {
+        let notes: Vec<String> = error.notes().iter()
+            .map(|note| format!("{{\"message\":{},\"file\":{},\"span\":{}}}",
+                json::quote(&note.message),
+                json_file(note.span.as_ref()),
+                json_span(note.span.as_ref())))
+            .collect();
+        let trace: Vec<String> = error.trace().iter()
+            .map(|frame| format!("{{\"function\":{},\"file\":{},\"line\":{}}}",
+                json::quote(&frame.function),
+                json::quote(&frame.span.file),
+                frame.span.line))
+            .collect();
+        let trace = match trace.is_empty() {
+            true => String::new(),
+            false => format!(",\"trace\":[{}]", trace.join(",")),
+        };
+        format!(
+            "{{\"severity\":{},\"phase\":{},\"code\":{},\"message\":{},\"file\":{},\"span\":{},\"notes\":[{}]{}}}\n",
+            json::quote(severity),
+            json::quote(error.phase()),
+            json::quote(error.code()),
+            json::quote(&error.message()),
+            error.file().map_or("null".to_string(), json::quote),
+            json_span(error.span()),
+            notes.join(","),
+            trace,
+        )
+    }
+
+    //renders in the style of rustc:
+    //  error: Operand must be a number.
+    //   --> script.lax:2:7
+    //    |
+    //  2 | print -"a";
+    //    |       ^
+    //followed, for errors raised inside a function, by
+    //    = stack trace:
+    //        at f (script.lax:2)
+    //        at <script> (script.lax:4)
+    //where a frame repeated many times in a row is written once
+    pub fn render<E: Diagnostic>(&self, severity: &str, error: &E) -> String {
+        let mut out = format!("{}: {}\n", severity, error.message());
+        let gutter = match error.span() {
+            Some(span) => {
+                let gutter = " ".repeat(span.line.to_string().len());
+                out += &format!("{}--> {}\n", gutter, span);
+                out += &self.snippet(span, &gutter);
+                gutter
+            },
+            None => String::new(),
+        };
+        for note in error.notes() {
+            match &note.span {
+                Some(span) => out += &format!("{} = note: {} ({})\n", gutter, note.message, span),
+                None => out += &format!("{} = note: {}\n", gutter, note.message),
+            }
+        }
+        let trace = error.trace();
+        if !trace.is_empty() {
+            out += &format!("{} = stack trace:\n", gutter);
+        }
+        //deep recursion repeats one frame hundreds of times, so a run of
+        //the same frame is shown once with a count
+        let mut frames = trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            out += &format!("{}     {}\n", gutter, frame);
+            let line = frame.to_string();
+            let mut repeats = 0;
+            while frames.next_if(|next| next.to_string() == line).is_some() {
+                repeats += 1;
+            }
+            match repeats {
+                0 => {},
+                1 => out += &format!("{}     {}\n", gutter, frame),
+                _ => out += &format!("{}     ... {} more frames in {}\n", gutter, repeats, frame.function),
+            }
+        }
+        out
+    }
+
+    fn snippet(&self, span: &Span, gutter: &str) -> String {
+        let source = match self.sources.get(span.file.as_str()) {
+            Some(source) => source,
+            None => return String::new(),
+        };
+        let text = match source.lines().nth(span.line.saturating_sub(1)) {
+            Some(text) => text,
+            None => return String::new(),
+        };
+        //keep tabs so the caret lines up with the quoted source
+        let indent: String = text.chars()
+            .take(span.column.saturating_sub(1))
+            .map(|c| if c == '\t' {'\t'} else {' '})
+            .collect();
+        let width = source.get(span.start..span.end)
+            .map_or(0, |range| range.chars().take_while(|c| *c != '\n').count())
+            .max(1);
+        format!("{} |\n{} | {}\n{} | {}{}\n",
+            gutter, span.line, text, gutter, indent, "^".repeat(width))
+    }
+}
+
+fn json_file(span: Option<&Span>) -> String {
+    span.map_or("null".to_string(), |span| json::quote(&span.file))
+}
+
+fn json_span(span: Option<&Span>) -> String {
+    match span {
+        Some(span) => format!("{{\"start\":{},\"end\":{},\"line\":{},\"column\":{}}}",
+            span.start, span.end, span.line, span.column),
+        None => "null".to_string(),
+    }
+}
+
+//extra context attached to a diagnostic, such as where a name was first declared
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Note {
+    pub fn new(message: &str, span: Option<Span>) -> Self {
+        Note { message: message.to_string(), span }
+    }
+}
+
+//anything that can be reported through ErrorStatus
+pub trait Diagnostic {
+    fn message(&self) -> String;
+    fn span(&self) -> Option<&Span>;
+
+    //names the phase that raised the diagnostic: io, scan, parse, resolve,
+    //type, compile or runtime
+    fn phase(&self) -> &'static str;
+
+    //names the kind of diagnostic so tools can filter on it without matching
+    //the message. errors are named after what raised them, like
+    //"runtime-error", and warnings after what they warn about
+    fn code(&self) -> &'static str;
+
+    //the file the diagnostic is about, even when there's no span in it
+    fn file(&self) -> Option<&str> {
+        self.span().map(|span| span.file.as_str())
+    }
+
+    fn notes(&self) -> Vec<Note> {
+        vec!()
+    }
+
+    fn trace(&self) -> Vec<TraceFrame> {
+        vec!()
+    }
+}
+
+impl<E: Diagnostic + ?Sized> Diagnostic for &E {
+    fn message(&self) -> String {
+        (**self).message()
+    }
+
+    fn span(&self) -> Option<&Span> {
+        (**self).span()
+    }
+
+    fn phase(&self) -> &'static str {
+        (**self).phase()
+    }
+
+    fn code(&self) -> &'static str {
+        (**self).code()
+    }
+
+    fn file(&self) -> Option<&str> {
+        (**self).file()
+    }
+
+    fn notes(&self) -> Vec<Note> {
+        (**self).notes()
+    }
+
+    fn trace(&self) -> Vec<TraceFrame> {
+        (**self).trace()
+    }
+}
+
+impl Default for ErrorStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub enum LaxError {
+    Io(io::Error),
+    Compile,
+    Runtime(RuntimeError),
+}
+
+impl LaxError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LaxError::Io(_) => 1,
+            LaxError::Compile => 65,
+            LaxError::Runtime(_) => 70,
+        }
+    }
+}
+
+impl Error for LaxError {}
+
+impl fmt::Display for LaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LaxError::Io(error) => write!(f, "Could not read file: {}", error),
+            LaxError::Compile => write!(f, "Source failed to compile."),
+            LaxError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct RuntimeError {
-    token: Rc<Token>,
+    span: Option<Span>,
     message: String,
+    thrown: Option<Box<Value>>, //set by `throw`, otherwise the error came from the runtime
+    trace: Vec<TraceFrame>, //innermost first, empty for errors raised outside any call
 }
 
 impl RuntimeError {
     pub fn new(token: Rc<Token>, message: &str) -> Self {
-        RuntimeError { token, message: message.to_string() }
+        RuntimeError::at_span(token.span.clone(), message)
+    }
+
+    pub fn at_span(span: Span, message: &str) -> Self {
+        RuntimeError { span: Some(span), message: message.to_string(), thrown: None, trace: vec!() }
+    }
+
+    //for errors raised by native functions, which have no token of their own
+    pub fn without_token(message: &str) -> Self {
+        RuntimeError { span: None, message: message.to_string(), thrown: None, trace: vec!() }
+    }
+
+    //rethrowing a caught error keeps the message and location it was raised with
+    pub fn thrown(value: Value, span: Span) -> Self {
+        let (message, span) = match &value {
+            Value::Exception(exception) =>
+                (exception.message.clone(), exception.span.clone()),
+            _ => (value.to_string(), Some(span)),
+        };
+        RuntimeError { span, message, thrown: Some(Box::new(value)), trace: vec!() }
+    }
+
+    //what a catch clause binds: the thrown value, or an error object
+    pub fn value(&self) -> Value {
+        match &self.thrown {
+            Some(value) => *value.clone(),
+            None => Value::Exception(Arc::new(Exception {
+                message: self.message.clone(),
+                span: self.span.clone(),
+            })),
+        }
+    }
+
+    pub fn or_at(self, token: Rc<Token>) -> Self {
+        self.or_at_span(token.span.clone())
+    }
+
+    pub fn or_at_span(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    //the trace is taken where the error first leaves a function, so later
+    //calls it unwinds through don't replace it
+    pub fn with_trace(mut self, trace: impl FnOnce(Option<&Span>) -> Vec<TraceFrame>) -> Self {
+        if self.trace.is_empty() {
+            self.trace = trace(self.span.as_ref());
+        }
+        self
+    }
+}
+
+//one line of a stack trace: the function that was running and where
+#[derive(Debug, Clone)]
+pub struct TraceFrame {
+    pub function: String,
+    pub span: Span,
+}
+
+impl TraceFrame {
+    pub fn new(function: &str, span: Span) -> Self {
+        let function = match function.is_empty() {
+            true => "<script>".to_string(),
+            false => function.to_string(),
+        };
+        TraceFrame { function, span }
+    }
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at {} ({}:{})", self.function, self.span.file, self.span.line)
     }
 }
 
 impl Error for RuntimeError {}
 
+impl Diagnostic for RuntimeError {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    fn phase(&self) -> &'static str {
+        "runtime"
+    }
+
+    //a value thrown by the script and never caught is told apart from an
+    //error the runtime raised itself
+    fn code(&self) -> &'static str {
+        match self.thrown {
+            Some(_) => "uncaught-throw",
+            None => "runtime-error",
+        }
+    }
+
+    fn trace(&self) -> Vec<TraceFrame> {
+        self.trace.clone()
+    }
+}
+
 impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[line {}] {}", self.token.line, self.message)
+        match &self.span {
+            Some(span) => write!(f, "[line {}] {}", span.line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+//a built in runtime error once it has been caught
+#[derive(Debug)]
+pub struct Exception {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Exception {
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match name {
+            "message" => Some(Value::String(self.message.clone())),
+            "line" => Some(match &self.span {
+                Some(span) => Value::Num(span.line as f64),
+                None => Value::None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error: {}", self.message)
+    }
+}
+
+//a file that couldn't be read or written, reported like any other
+//diagnostic so it follows the error format
+#[derive(Debug)]
+pub struct IoError {
+    pub file: String,
+    pub message: String,
+}
+
+impl IoError {
+    pub fn new(file: &str, action: &str, error: &io::Error) -> Self {
+        let message = format!("Could not {} '{}': {}", action, file, error);
+        IoError { file: file.to_string(), message }
+    }
+}
+
+impl Error for IoError {}
+
+impl Diagnostic for IoError {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn span(&self) -> Option<&Span> {
+        None
+    }
+
+    fn phase(&self) -> &'static str {
+        "io"
+    }
+
+    fn code(&self) -> &'static str {
+        "io-error"
+    }
+
+    fn file(&self) -> Option<&str> {
+        Some(&self.file)
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
     }
 }
 
 #[derive(Debug)]
 pub struct ScanError {
-    pub line: usize,
+    pub span: Span,
     pub message: String,
 }
 
 impl ScanError {
-    pub fn new(line: usize, message: &str) -> Self {
-        ScanError { line, message: message.to_string() }
+    pub fn new(span: Span, message: &str) -> Self {
+        ScanError { span, message: message.to_string() }
     }
 }
 
 impl Error for ScanError {}
 
+impl Diagnostic for ScanError {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn span(&self) -> Option<&Span> {
+        Some(&self.span)
+    }
+
+    fn phase(&self) -> &'static str {
+        "scan"
+    }
+
+    fn code(&self) -> &'static str {
+        "scan-error"
+    }
+}
+
 impl fmt::Display for ScanError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[line {}] Error: {}", self.line, self.message)
+        write!(f, "[line {}] Error: {}", self.span.line, self.message)
     }
 }
 
@@ -84,17 +592,201 @@ impl ParseError {
 
 impl Error for ParseError {}
 
+impl Diagnostic for ParseError {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn span(&self) -> Option<&Span> {
+        Some(&self.token.span)
+    }
+
+    fn phase(&self) -> &'static str {
+        "parse"
+    }
+
+    fn code(&self) -> &'static str {
+        "parse-error"
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.token.token_type == TokenType::Eof {
             true => {
                 write!(f, "[line {}] Error at end: {}",
-                    self.token.line, self.message)
+                    self.token.line(), self.message)
             },
             false => { 
                 write!(f, "[line {}] Error at '{}': {}",
-                    self.token.line, self.token.lexeme, self.message)
+                    self.token.line(), self.token.lexeme, self.message)
             }
         }
     }
 }
+
+#[derive(Debug)]
+pub struct CompileError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl CompileError {
+    pub fn new(span: Span, message: &str) -> Self {
+        CompileError { span, message: message.to_string() }
+    }
+}
+
+impl Error for CompileError {}
+
+impl Diagnostic for CompileError {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn span(&self) -> Option<&Span> {
+        Some(&self.span)
+    }
+
+    fn phase(&self) -> &'static str {
+        "compile"
+    }
+
+    fn code(&self) -> &'static str {
+        "compile-error"
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] Error: {}", self.span.line, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub struct TypeError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl TypeError {
+    pub fn new(span: Span, message: &str) -> Self {
+        TypeError { span, message: message.to_string() }
+    }
+}
+
+impl Error for TypeError {}
+
+impl Diagnostic for TypeError {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn span(&self) -> Option<&Span> {
+        Some(&self.span)
+    }
+
+    fn phase(&self) -> &'static str {
+        "type"
+    }
+
+    fn code(&self) -> &'static str {
+        "type-error"
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] Error: {}", self.span.line, self.message)
+    }
+}
+
+//something legal but probably a mistake, like a variable that is never read
+#[derive(Debug)]
+pub struct Warning {
+    pub code: &'static str,
+    pub span: Span,
+    pub message: String,
+    pub notes: Vec<Note>,
+}
+
+impl Warning {
+    pub fn new(code: &'static str, span: Span, message: &str) -> Self {
+        Warning { code, span, message: message.to_string(), notes: vec!() }
+    }
+
+    pub fn with_note(mut self, note: Note) -> Self {
+        self.notes.push(note);
+        self
+    }
+}
+
+impl Diagnostic for Warning {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn span(&self) -> Option<&Span> {
+        Some(&self.span)
+    }
+
+    fn phase(&self) -> &'static str {
+        "resolve"
+    }
+
+    fn code(&self) -> &'static str {
+        self.code
+    }
+
+    fn notes(&self) -> Vec<Note> {
+        self.notes.clone()
+    }
+}
+
+#[derive(Debug)]
+pub struct ResolveError {
+    pub span: Span,
+    pub message: String,
+    pub notes: Vec<Note>,
+}
+
+impl ResolveError {
+    pub fn new(token: Rc<Token>, message: &str) -> Self {
+        ResolveError { span: token.span.clone(), message: message.to_string(), notes: vec!() }
+    }
+
+    pub fn with_note(mut self, note: Note) -> Self {
+        self.notes.push(note);
+        self
+    }
+}
+
+impl Error for ResolveError {}
+
+impl Diagnostic for ResolveError {
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    fn span(&self) -> Option<&Span> {
+        Some(&self.span)
+    }
+
+    fn phase(&self) -> &'static str {
+        "resolve"
+    }
+
+    fn code(&self) -> &'static str {
+        "resolve-error"
+    }
+
+    fn notes(&self) -> Vec<Note> {
+        self.notes.clone()
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] Error: {}", self.span.line, self.message)
+    }
+}
@@ -0,0 +1,236 @@
+use std::rc::Rc;
+
+use crate::token::{Token, TokenType};
+
+const INDENT: &str = "    ";
+
+//re-prints source in one canonical style: four space indents, a statement
+//per line, single spaces between tokens except inside brackets and around
+//`.`, calls and unary operators, and runs of blank lines cut down to one.
+//it works on tokens rather than the syntax tree, which has already lowered
+//for loops, interpolation and `|a| expr` lambdas, and puts the comments the
+//scanner kept as trivia back where they were
+pub struct Formatter {
+    out: String,
+    brackets: Vec<Bracket>,
+    last: Option<(TokenType, Role)>,
+    last_line: usize, //the line the last token or comment ended on
+    line_break: bool, //the next token starts a new line
+    after_comment: bool,
+    lambda: bool, //a `fn` without a name was seen, so the next block is its body
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bracket {
+    Paren,
+    Square,
+    Map,
+    Block,
+    Lambda, //the body of `fn (a) { ... }`, which carries on the expression after it
+    Params, //between the pipes of `|a, b| expr`
+}
+
+//what a token means for the spacing around whatever comes next
+#[derive(Clone, Copy, PartialEq)]
+enum Role {
+    Operand, //ends an operand, so a `(` after it is a call and a `-` is binary
+    Prefix, //nothing is spaced after it, like `(` or a unary `-`
+    BlockStart,
+    BlockEnd,
+    LambdaEnd,
+    Other,
+}
+
+impl Formatter {
+    //the tokens must parse, so every bracket they open is closed
+    pub fn format(tokens: &[Rc<Token>], trivia: &[Rc<Token>]) -> String {
+        let mut formatter = Formatter {
+            out: String::new(),
+            brackets: vec!(),
+            last: None,
+            last_line: 1,
+            line_break: false,
+            after_comment: false,
+            lambda: false,
+        };
+        let mut comments = trivia.iter().peekable();
+        for token in tokens {
+            while let Some(comment) = comments.next_if(|c| c.span.start < token.span.start) {
+                formatter.comment(comment);
+            }
+            if token.token_type != TokenType::Eof {
+                formatter.token(token);
+            }
+        }
+        for comment in comments {
+            formatter.comment(comment);
+        }
+        if !formatter.out.is_empty() {
+            formatter.out.push('\n');
+        }
+        formatter.out
+    }
+
+    fn token(&mut self, token: &Token) {
+        let (role, glued) = self.classify(token);
+        if self.line_break && !self.after_comment && self.joins(token, role) {
+            self.line_break = false;
+        }
+        if self.line_break {
+            self.newline(token.span.line, role);
+        } else if !self.out.is_empty() && !glued && !self.glues_next(token) {
+            self.out.push(' ');
+        }
+        self.out.push_str(&token.lexeme);
+
+        self.last_line = token.span.line + token.lexeme.matches('\n').count();
+        self.line_break = match token.token_type {
+            TokenType::Semicolon => matches!(self.brackets.last(),
+                None | Some(Bracket::Block) | Some(Bracket::Lambda)),
+            _ => matches!(role, Role::BlockStart | Role::BlockEnd),
+        };
+        self.after_comment = false;
+        self.last = Some((token.token_type.clone(), role));
+    }
+
+    //a comment on the same line as the code before it stays there, any other
+    //gets a line of its own. either way the next token starts a new line
+    fn comment(&mut self, comment: &Token) {
+        if self.out.is_empty() {
+        } else if comment.span.line == self.last_line {
+            self.out.push(' ');
+        } else {
+            self.newline(comment.span.line, Role::Other);
+        }
+        self.out.push_str(comment.lexeme.trim_end());
+        self.last_line = comment.span.line;
+        self.line_break = true;
+        self.after_comment = true;
+    }
+
+    //breaks the line before something starting on `line` of the source,
+    //keeping one blank line if the source had any. a line that continues a
+    //statement broken by a comment is indented one level further
+    fn newline(&mut self, line: usize, role: Role) {
+        self.out.push('\n');
+        let opened_block = !self.after_comment && self.last_role() == Some(Role::BlockStart);
+        let closes_block = matches!(role, Role::BlockEnd | Role::LambdaEnd);
+        if line > self.last_line + 1 && !opened_block && !closes_block {
+            self.out.push('\n');
+        }
+        let continued = match &self.last {
+            Some((TokenType::Semicolon, _)) | None => false,
+            Some((_, role)) => !matches!(role, Role::BlockStart | Role::BlockEnd),
+        };
+        //a block that was just opened is indented from the line after it
+        let blocks = self.brackets.iter()
+            .filter(|b| matches!(b, Bracket::Block | Bracket::Lambda))
+            .count() - (role == Role::BlockStart) as usize;
+        self.out.push_str(&INDENT.repeat(blocks + continued as usize));
+    }
+
+    //whether a token that would start a new line belongs on the last one
+    //instead, like the `else` in `} else {` or the `}` of an empty block
+    fn joins(&self, token: &Token, role: Role) -> bool {
+        match &self.last {
+            Some((TokenType::Semicolon, _)) => token.token_type == TokenType::Else,
+            Some((_, Role::BlockStart)) => matches!(role, Role::BlockEnd | Role::LambdaEnd),
+            Some((_, Role::BlockEnd)) => matches!(token.token_type,
+                TokenType::Else | TokenType::Catch | TokenType::Finally),
+            _ => false,
+        }
+    }
+
+    //whether the last token is written right up against `token`. a prefix
+    //sign keeps its space before another, since `- -x` would rescan as `--x`
+    fn glues_next(&self, token: &Token) -> bool {
+        match &self.last {
+            Some((last, Role::Prefix)) => !(is_sign(last) && is_sign(&token.token_type)),
+            Some((_, Role::BlockStart)) => true,
+            Some((_, Role::Operand)) | Some((_, Role::LambdaEnd)) => matches!(token.token_type,
+                TokenType::OpenParen | TokenType::OpenBracket),
+            _ => false,
+        }
+    }
+
+    fn last_role(&self) -> Option<Role> {
+        self.last.as_ref().map(|(_, role)| *role)
+    }
+
+    fn is_operand(&self) -> bool {
+        matches!(self.last_role(), Some(Role::Operand) | Some(Role::LambdaEnd))
+    }
+
+    //a `{` opens a block wherever a statement or a declaration's body can
+    //start, and a map anywhere else
+    fn is_block(&self) -> bool {
+        match &self.last {
+            None => true,
+            Some((_, Role::BlockStart)) | Some((_, Role::BlockEnd)) => true,
+            Some((token_type, _)) => matches!(token_type,
+                TokenType::Semicolon | TokenType::CloseParen | TokenType::Identifier
+                | TokenType::Nil | TokenType::Else | TokenType::Try | TokenType::Finally),
+        }
+    }
+
+    //the token's role, and whether it's written right up against the token
+    //before it. keeps track of the brackets it opens and closes
+    fn classify(&mut self, token: &Token) -> (Role, bool) {
+        match token.token_type {
+            TokenType::OpenParen => {self.brackets.push(Bracket::Paren); (Role::Prefix, false)},
+            TokenType::OpenBracket => {self.brackets.push(Bracket::Square); (Role::Prefix, false)},
+            TokenType::CloseParen | TokenType::CloseBracket => {
+                self.brackets.pop();
+                (Role::Operand, true)
+            },
+            TokenType::OpenBrace if self.is_block() => {
+                match std::mem::take(&mut self.lambda) {
+                    true => self.brackets.push(Bracket::Lambda),
+                    false => self.brackets.push(Bracket::Block),
+                }
+                (Role::BlockStart, false)
+            },
+            TokenType::OpenBrace => {self.brackets.push(Bracket::Map); (Role::Prefix, false)},
+            TokenType::CloseBrace => match self.brackets.pop() {
+                Some(Bracket::Map) => (Role::Operand, true),
+                Some(Bracket::Lambda) => (Role::LambdaEnd, false),
+                _ => (Role::BlockEnd, false),
+            },
+            TokenType::Fn => {self.lambda = true; (Role::Other, false)},
+            TokenType::Identifier if self.last.as_ref()
+                .is_some_and(|(token_type, _)| *token_type == TokenType::Fn) => {
+                self.lambda = false;
+                (Role::Operand, false)
+            },
+            TokenType::Pipe => match self.brackets.last() {
+                Some(Bracket::Params) => {self.brackets.pop(); (Role::Other, true)},
+                _ if !self.is_operand() => {
+                    self.brackets.push(Bracket::Params);
+                    (Role::Prefix, false)
+                },
+                _ => (Role::Other, false),
+            },
+            TokenType::Minus if !self.is_operand() => (Role::Prefix, false),
+            TokenType::PlusPlus | TokenType::MinusMinus => match self.is_operand() {
+                true => (Role::Operand, true),
+                false => (Role::Prefix, false),
+            },
+            TokenType::Bang => (Role::Prefix, false),
+            TokenType::Dot => (Role::Prefix, true),
+            TokenType::Comma | TokenType::Semicolon | TokenType::Colon => (Role::Other, true),
+            //a segment after the first starts with the `}` closing an
+            //interpolated expression
+            TokenType::Interpolation => (Role::Prefix, token.lexeme.starts_with('}')),
+            TokenType::String => (Role::Operand, token.lexeme.starts_with('}')),
+            TokenType::Identifier | TokenType::Number | TokenType::True | TokenType::False
+            | TokenType::Nil | TokenType::This | TokenType::Super => (Role::Operand, false),
+            _ => (Role::Other, false),
+        }
+    }
+}
+
+//a token that two of would scan as one, like `-` and `-`
+fn is_sign(token_type: &TokenType) -> bool {
+    matches!(token_type, TokenType::Minus | TokenType::Plus
+        | TokenType::MinusMinus | TokenType::PlusPlus)
+}
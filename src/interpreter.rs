@@ -1,5 +1,8 @@
 use std::{
-    collections::HashMap, 
+    collections::{HashMap, BTreeMap},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    fs,
     rc::Rc,
     sync::{Arc, Mutex, RwLock},
 };
@@ -9,45 +12,78 @@ use crate::{
         native_functions::NativeDeclarations,
         lax_functions::LaxFn,
         Callable,
+        Arity,
     },
     ast::{
         expr::{self, Expr, AcceptExprVisitor, ExprVisitor},
         stmt::{self, Stmt, AcceptStmtVisitor, StmtVisitor},
     },
-    token::{Value, TokenType},
+    token::{Value, TokenType, MapKey, Span},
     environment::Environment,
-    error::RuntimeError,
+    error::{RuntimeError, TraceFrame},
+    module::{Module, module_path},
     token::Token,
 };
 
+//how a statement finished, so return, break and continue can unwind
+//through the blocks that enclose them
+pub enum Flow {
+    Normal,
+    Return(Arc<RwLock<Value>>),
+    Break(Option<Rc<String>>),
+    Continue(Option<Rc<String>>),
+}
+
+//a call in progress, kept so errors can report how they were reached
+struct Frame {
+    function: String,
+    call_site: Rc<Token>,
+}
+
 pub struct Interpreter {
     pub globals: Arc<Mutex<Environment>>,
     environment: Arc<Mutex<Environment>>,
-    locals: HashMap<u64, usize>
+    locals: HashMap<u64, usize>,
+    modules: HashMap<PathBuf, Arc<Module>>, //keyed by canonical path
+    natives: HashMap<String, Arc<RwLock<Value>>>, //every native ever defined, by name
+    frames: Vec<Frame>,
+    output: Box<dyn Write>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut globals = Environment::new_wrapped(None);
-        let mut native = NativeDeclarations::new(Arc::clone(&globals));
-        globals = native.declare_natives();
-        let environment = Environment::new_wrapped(Some(Arc::clone(&globals)));
-        
-        Interpreter {
+        let globals = Environment::new_wrapped(None);
+        let environment = Arc::clone(&globals);
+
+        let mut interpreter = Interpreter {
             globals,
             environment,
             locals: HashMap::new(),
-        }
+            modules: HashMap::new(),
+            natives: HashMap::new(),
+            frames: vec!(),
+            output: Box::new(io::stdout()),
+        };
+        NativeDeclarations::new(&mut interpreter).declare_natives();
+        interpreter
     }
 
-    pub fn interpret(&mut self, stmts: &Vec<Stmt>) -> Result<(), RuntimeError>{
+    //returns the value of the final statement if it is an expression
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<Arc<RwLock<Value>>, RuntimeError>{
+        let mut last = Arc::new(RwLock::new(Value::None));
         for stmt in stmts {
-            self.execute(stmt)?;
+            last = match stmt {
+                Stmt::StmtExpr(stmt) => self.evaluate(&stmt.expr)?,
+                _ => {
+                    self.execute(stmt)?;
+                    Arc::new(RwLock::new(Value::None))
+                },
+            };
         };
-        Ok(())
+        Ok(last)
     }
 
-    fn execute(&mut self, stmt: &Stmt) -> Result<Option<Arc<RwLock<Value>>>, RuntimeError> {
+    fn execute(&mut self, stmt: &Stmt) -> Result<Flow, RuntimeError> {
         stmt.accept(self)
     }
 
@@ -55,21 +91,113 @@ impl Interpreter {
         expr.accept(self)
     }
 
+    //redirects the output of print statements
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    pub fn output(&mut self) -> &mut dyn Write {
+        &mut *self.output
+    }
+
+    //drops every global and loaded module, keeping only the natives
+    pub fn reset(&mut self) {
+        self.globals = self.module_globals();
+        self.environment = Arc::clone(&self.globals);
+        self.locals.clear();
+        self.modules.clear();
+        self.frames.clear();
+    }
+
+    pub fn print(&mut self, value: &Value) -> Result<(), RuntimeError> {
+        writeln!(self.output, "{}", value).map_err(|error| {
+            let message = format!("Failed to write output: {}", error);
+            RuntimeError::without_token(&message)
+        })
+    }
+
+    pub fn define_native<F>(&mut self, name: &str, arity: Arity, func: F)
+    where F: Fn(&mut Interpreter, Vec<Arc<RwLock<Value>>>) -> Result<Value, RuntimeError> + 'static {
+        let callable = Callable::new_native_fn(name.to_string(), Rc::new(func), arity);
+        let value = Arc::new(RwLock::new(Value::Callable(callable)));
+        self.natives.insert(name.to_string(), Arc::clone(&value));
+        self.globals.lock().unwrap().define(name.to_string(), value);
+    }
+
+    //fresh globals for a module, holding the session's natives
+    pub fn module_globals(&self) -> Arc<Mutex<Environment>> {
+        let mut env = Environment::new(None);
+        for (name, value) in &self.natives {
+            env.define(name.to_string(), Arc::clone(value));
+        }
+        Arc::new(Mutex::new(env))
+    }
+
+    //runs top level code in other globals, returning the ones it replaced
+    pub fn replace_globals(&mut self, globals: Arc<Mutex<Environment>>
+        ) -> Arc<Mutex<Environment>> {
+        self.environment = Arc::clone(&globals);
+        std::mem::replace(&mut self.globals, globals)
+    }
+
+    pub fn add_module(&mut self, path: PathBuf, module: Arc<Module>) {
+        self.modules.insert(path, module);
+    }
+
+    pub fn loaded_module(&self, path: &Path) -> Option<Arc<Module>> {
+        self.modules.get(path).cloned()
+    }
+
+    //modules are loaded before the code importing them runs
+    pub fn module_at(&self, path: &Path) -> Result<Arc<Module>, String> {
+        fs::canonicalize(path).ok()
+            .and_then(|path| self.loaded_module(&path))
+            .ok_or_else(|| format!("Module '{}' has not been loaded.", path.display()))
+    }
+
+    //innermost first. each frame is shown at the line it had reached: the
+    //error itself for the innermost, the call into the next frame otherwise
+    fn trace(&self, error: Option<&Span>) -> Vec<TraceFrame> {
+        let mut trace = vec!();
+        let mut span = error.cloned();
+        for frame in self.frames.iter().rev() {
+            if let Some(span) = span {
+                trace.push(TraceFrame::new(&frame.function, span));
+            }
+            span = Some(frame.call_site.span.clone());
+        }
+        if let Some(span) = span {
+            trace.push(TraceFrame::new("", span));
+        }
+        trace
+    }
+
+    fn module(&self, path: &Rc<Token>) -> Result<Arc<Module>, RuntimeError> {
+        self.module_at(&module_path(path))
+            .map_err(|message| RuntimeError::new(Rc::clone(path), &message))
+    }
+
     pub fn resolve(&mut self, id: u64, depth: usize) {
         self.locals.insert(id, depth);
     }
 
+    //how many scopes out each resolved expression finds its variable
+    pub fn locals(&self) -> &HashMap<u64, usize> {
+        &self.locals
+    }
+
     pub fn execute_block(&mut self, stmts: &Vec<Stmt>, env: Arc<Mutex<Environment>>
-        ) -> Result<Option<Arc<RwLock<Value>>>, RuntimeError> {
+        ) -> Result<Flow, RuntimeError> {
         let prev = std::mem::replace(&mut self.environment, env);
+        let mut flow = Ok(Flow::Normal);
         for stmt in stmts {
-            if let Some(val) = self.execute(stmt)? {
-                self.environment = prev;
-                return Ok(Some(val))
+            flow = self.execute(stmt);
+            if !matches!(flow, Ok(Flow::Normal)) {
+                break
             }
         }
         self.environment = prev;
-        Ok(None)
+        flow
     }
 
     fn lookup_variable(&self, token: Rc<Token>, id: u64
@@ -81,17 +209,43 @@ impl Interpreter {
                     .unwrap()
                     .get_at(depth.to_owned(), token)
             },
-            None => self.globals.lock().unwrap().get(token),
+            None => Environment::root(&self.environment).lock().unwrap().get(token),
         }
     }
 
-    fn is_truthy(&self, value: &Arc<RwLock<Value>>) -> bool {
-        match *value.read().unwrap() {
-            Value::Bool(val) => val.to_owned(),
-            Value::None => false,
-            _ => true,
+    //evaluates the value to store in an assignment target. for `a += b` and
+    //`a++` the target's current value is combined with the right hand side
+    fn assigned_value(&mut self, current: &Option<Arc<RwLock<Value>>>, value: &Expr,
+        update: &Option<expr::Update>) -> Result<Arc<RwLock<Value>>, RuntimeError> {
+        let value = self.evaluate(value)?;
+        let (update, current) = match (update, current) {
+            (Some(update), Some(current)) => (update, current),
+            _ => return Ok(value),
+        };
+        let new_value = current.read().unwrap()
+            .binary(&update.operator.token_type, &value.read().unwrap())
+            .map_err(|message| RuntimeError::new(Rc::clone(&update.operator), message))?;
+        Ok(Arc::new(RwLock::new(new_value)))
+    }
+
+    //postfix updates evaluate to the target's old value
+    fn assignment_result(current: Option<Arc<RwLock<Value>>>, stored: &Arc<RwLock<Value>>,
+        update: &Option<expr::Update>) -> Arc<RwLock<Value>> {
+        match (current, update) {
+            (Some(current), Some(update)) if update.postfix => current,
+            _ => Arc::clone(stored),
         }
     }
+
+    fn is_truthy(&self, value: &Arc<RwLock<Value>>) -> bool {
+        value.read().unwrap().is_truthy()
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ExprVisitor for Interpreter {
@@ -110,62 +264,19 @@ impl ExprVisitor for Interpreter {
     fn visit_unary_expr(&mut self, expr: &expr::Unary
         ) -> Self::Output {
         let binding = self.evaluate(&expr.right)?;
-        let output = binding.read().unwrap();
-        let token_type = &expr.operator.token_type;
-
-        let value = match (&*output, token_type) {
-            (Value::Num(val), TokenType::Minus) => Value::Num(-val),
-            (_, TokenType::Minus) => return Err(
-                RuntimeError::new(Rc::clone(&expr.operator), "Operand must be a number.")
-            ),
-            (_, TokenType::Bang) => Value::Bool(!self.is_truthy(&binding)),
-            _ => Value::None,
-        };
+        let value = binding.read().unwrap()
+            .unary(&expr.operator.token_type)
+            .map_err(|message| RuntimeError::new(Rc::clone(&expr.operator), message))?;
         Ok(Arc::new(RwLock::new(value)))
     }
 
     fn visit_binary_expr(&mut self, expr: &expr::Binary
         ) -> Self::Output {
-        let binding = self.evaluate(&expr.left)?;
-        let left = binding.read().unwrap();
-        let binding = self.evaluate(&expr.right)?;
-        let right = binding.read().unwrap();
-        let token_type = &expr.operator.token_type;
-        let value = match (&*left, &*right) {
-            (Value::Num(left), Value::Num(right)) => match token_type {
-                TokenType::Plus => Value::Num(left + right),
-                TokenType::Minus => Value::Num(left - right),
-                TokenType::Star => Value::Num(left * right),
-                TokenType::Slash => Value::Num(left / right),
-                TokenType::Greater => Value::Bool(left > right),
-                TokenType::GreaterEqual => Value::Bool(left >= right),
-                TokenType::Less => Value::Bool(left < right),
-                TokenType::LessEqual => Value::Bool(left <= right),
-                TokenType::EqualEqual => Value::Bool(left == right),
-                _ => return Err(RuntimeError::new(
-                    Rc::clone(&expr.operator),
-                    "Operator cannot be used on numbers"
-                )),
-            },
-
-            (Value::String(left), Value::String(right)) => match token_type {
-                TokenType::Plus => Value::String(left.to_string() + right),
-                TokenType::EqualEqual => Value::Bool(left == right),
-                _ => return Err(RuntimeError::new(
-                    Rc::clone(&expr.operator),
-                    "Operator cannot be used on strings"
-                ))
-            },
-
-            (left, right) => match token_type {
-                TokenType::EqualEqual => Value::Bool(left == right),
-                TokenType::BangEqual => Value::Bool(left != right),
-                _ => return Err(RuntimeError::new(
-                    Rc::clone(&expr.operator),
-                    "Operator cannot be used on values of this type"
-                ))
-            }
-        };
+        let left = self.evaluate(&expr.left)?;
+        let right = self.evaluate(&expr.right)?;
+        let value = left.read().unwrap()
+            .binary(&expr.operator.token_type, &right.read().unwrap())
+            .map_err(|message| RuntimeError::new(Rc::clone(&expr.operator), message))?;
         Ok(Arc::new(RwLock::new(value)))
     }
 
@@ -174,23 +285,27 @@ impl ExprVisitor for Interpreter {
     }
 
     fn visit_assign_expr(&mut self, expr: &expr::Assign) -> Self::Output {
-        let value = self.evaluate(&expr.value)?;
+        let current = match &expr.update {
+            Some(_) => Some(self.lookup_variable(Rc::clone(&expr.token), expr.id)?),
+            None => None,
+        };
+        let value = self.assigned_value(&current, &expr.value, &expr.update)?;
+        let result = Self::assignment_result(current, &value, &expr.update);
         match self.locals.get(&expr.id) {
             Some(distance) => {
                 self.environment
                     .lock()
                     .unwrap()
-                    .assign_at(distance.to_owned(), Rc::clone(&expr.token), Arc::clone(&value))?;
+                    .assign_at(distance.to_owned(), Rc::clone(&expr.token), value)?;
             },
             None => {
-                self.globals
+                Environment::root(&self.environment)
                     .lock()
                     .unwrap()
-                    .assign(Rc::clone(&expr.token), Arc::clone(&value))?;
+                    .assign(Rc::clone(&expr.token), value)?;
             },
         }
-        Ok(value)
-         
+        Ok(result)
     }
 
     fn visit_logical_expr(&mut self, expr: &expr::Logical) -> Self::Output {
@@ -223,12 +338,34 @@ impl ExprVisitor for Interpreter {
             args.push(self.evaluate(arg)?);
         }
         
-        if args.len() != function.arity() {
-            let message = format!("Expected {} arguments but got {}.",
-                                  function.arity(), args.len());
-            return Err(RuntimeError::new(Rc::clone(&expr.paren), &message))
+        let arity = function.arity();
+        if !arity.accepts(args.len()) {
+            return Err(RuntimeError::new(Rc::clone(&expr.paren), &arity.mismatch(args.len())))
+        }
+        let frame = match function {
+            Callable::NativeFn(_) => None,
+            Callable::LaxFn(func) => Some(Frame {
+                function: func.declaration.token.lexeme.to_string(),
+                call_site: Rc::clone(&expr.paren),
+            }),
+            //only an initializer runs code when a class is called
+            Callable::LaxClass(_) => Some(Frame {
+                function: "init".to_string(),
+                call_site: Rc::clone(&expr.paren),
+            }),
+        };
+        let traced = frame.is_some();
+        self.frames.extend(frame);
+        let result = function.call(self, args)
+            .map_err(|error| error.or_at(Rc::clone(&expr.paren)))
+            .map_err(|error| match traced {
+                true => error.with_trace(|span| self.trace(span)),
+                false => error,
+            });
+        if traced {
+            self.frames.pop();
         }
-        function.call(self, args)
+        result
     }
 
     fn visit_get_expr(&mut self, expr: &expr::Get) -> Self::Output {
@@ -238,6 +375,14 @@ impl ExprVisitor for Interpreter {
             Value::LaxObject(object) => {
                 Ok(object.lock().unwrap().get(Rc::clone(&expr.token), Arc::clone(&binding))?)
             },
+            Value::Module(module) => module.get(Rc::clone(&expr.token)),
+            Value::Exception(exception) => match exception.get(&expr.token.lexeme) {
+                Some(value) => Ok(Arc::new(RwLock::new(value))),
+                None => {
+                    let message = format!("Undefined property '{}'", expr.token.lexeme);
+                    Err(RuntimeError::new(Rc::clone(&expr.token), &message))
+                },
+            },
             _ => Err(RuntimeError::new(Rc::clone(&expr.token), 
                     "Only instances have properties."))
         }
@@ -245,19 +390,80 @@ impl ExprVisitor for Interpreter {
 
     fn visit_set_expr(&mut self, expr: &expr::Set) -> Self::Output {
         let binding = self.evaluate(&expr.object)?;
-        let value = binding.write().unwrap();
-
-        let object = match &*value {
+        //the guard must be released before the value, which may read the object
+        let object = match &*binding.read().unwrap() {
             Value::LaxObject(object) => Arc::clone(object),
             _ => return Err(RuntimeError::new(Rc::clone(&expr.token), 
                             "Only instances have fields."))
         };
         
-        let value = self.evaluate(&expr.value)?;
+        let current = match &expr.update {
+            Some(_) => Some(object.lock().unwrap().get(Rc::clone(&expr.token), Arc::clone(&binding))?),
+            None => None,
+        };
+        let value = self.assigned_value(&current, &expr.value, &expr.update)?;
+        let result = Self::assignment_result(current, &value, &expr.update);
         object.lock()
             .unwrap()
-            .set(Rc::clone(&expr.token), Arc::clone(&value));
-        Ok(value)
+            .set(Rc::clone(&expr.token), value);
+        Ok(result)
+    }
+
+    fn visit_list_expr(&mut self, expr: &expr::List) -> Self::Output {
+        let mut elements = vec!();
+        for element in &expr.elements {
+            elements.push(self.evaluate(element)?.read().unwrap().clone());
+        }
+        Ok(Arc::new(RwLock::new(Value::new_list(elements))))
+    }
+
+    fn visit_map_expr(&mut self, expr: &expr::Map) -> Self::Output {
+        let mut entries = BTreeMap::new();
+        for (key, value) in &expr.entries {
+            let key_span = key.span();
+            let key = self.evaluate(key)?.read().unwrap().clone();
+            let key = MapKey::from_value(&key)
+                .map_err(|message| RuntimeError::at_span(key_span, &message))?;
+            entries.insert(key, self.evaluate(value)?.read().unwrap().clone());
+        }
+        Ok(Arc::new(RwLock::new(Value::new_map(entries))))
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &expr::Lambda) -> Self::Output {
+        let env = Arc::clone(&self.environment);
+        let func = Callable::new_lax_fn(expr.func.clone(), env, false);
+        Ok(Arc::new(RwLock::new(Value::Callable(func))))
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &expr::Interpolation) -> Self::Output {
+        let mut text = String::new();
+        for part in &expr.parts {
+            text += &self.evaluate(part)?.read().unwrap().to_string();
+        }
+        Ok(Arc::new(RwLock::new(Value::String(text))))
+    }
+
+    fn visit_index_expr(&mut self, expr: &expr::Index) -> Self::Output {
+        let object = self.evaluate(&expr.object)?.read().unwrap().clone();
+        let index = self.evaluate(&expr.index)?.read().unwrap().clone();
+        let value = object.index(&index)
+            .map_err(|message| RuntimeError::new(Rc::clone(&expr.bracket), &message))?;
+        Ok(Arc::new(RwLock::new(value)))
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &expr::SetIndex) -> Self::Output {
+        let object = self.evaluate(&expr.object)?.read().unwrap().clone();
+        let index = self.evaluate(&expr.index)?.read().unwrap().clone();
+        let current = match &expr.update {
+            Some(_) => Some(Arc::new(RwLock::new(object.index(&index)
+                .map_err(|message| RuntimeError::new(Rc::clone(&expr.bracket), &message))?))),
+            None => None,
+        };
+        let value = self.assigned_value(&current, &expr.value, &expr.update)?;
+        let result = Self::assignment_result(current, &value, &expr.update);
+        object.set_index(&index, value.read().unwrap().clone())
+            .map_err(|message| RuntimeError::new(Rc::clone(&expr.bracket), &message))?;
+        Ok(result)
     }
 
     fn visit_this_expr(&mut self, expr: &expr::This) -> Self::Output {
@@ -278,7 +484,7 @@ impl ExprVisitor for Interpreter {
             TokenType::This,
             Rc::new("this".to_string()),
             Arc::new(RwLock::new(Value::None)),
-            expr.keyword.line
+            expr.keyword.span.clone()
         );
 
         let object = self.environment.lock().unwrap()
@@ -295,9 +501,9 @@ impl ExprVisitor for Interpreter {
         };
 
         if let Some(method_value) = method {
-            if let Value::Callable(Callable::LaxFn(method_callable)) = &*method_value.write().unwrap() {
-                method_callable.bind(object);
-                return Ok(Arc::clone(&method_value))
+            if let Value::Callable(Callable::LaxFn(method_callable)) = &*method_value.read().unwrap() {
+                let method = Callable::LaxFn(method_callable.bind(object));
+                return Ok(Arc::new(RwLock::new(Value::Callable(method))))
             }
         }
         Err(RuntimeError::new(Rc::clone(&expr.method),
@@ -306,20 +512,20 @@ impl ExprVisitor for Interpreter {
 }
 
 impl StmtVisitor for Interpreter {
-    type Output = Result<Option<Arc<RwLock<Value>>>, RuntimeError>;
+    type Output = Result<Flow, RuntimeError>;
 
     fn visit_expr_stmt(&mut self, stmt: &stmt::StmtExpr
         ) -> Self::Output {
         self.evaluate(&stmt.expr)?;
-        Ok(None)
+        Ok(Flow::Normal)
     }
 
     fn visit_print_stmt(&mut self, stmt: &stmt::Print
         ) -> Self::Output {
         let binding = self.evaluate(&stmt.expr)?;
         let value = binding.read().unwrap();
-        println!("{}", value);
-        Ok(None)
+        self.print(&value)?;
+        Ok(Flow::Normal)
     }
 
     fn visit_let_stmt(&mut self, stmt: &stmt::Let) -> Self::Output {
@@ -329,7 +535,7 @@ impl StmtVisitor for Interpreter {
         };
         self.environment.lock().unwrap()
             .define(stmt.token.lexeme.to_string(), value);
-        Ok(None)
+        Ok(Flow::Normal)
     }
 
     fn visit_block_stmt(&mut self, stmt: &stmt::Block) -> Self::Output {
@@ -345,21 +551,28 @@ impl StmtVisitor for Interpreter {
         } else if let Some(else_body) = &stmt.else_body {
             self.execute(else_body)
         } else {
-            Ok(None)
+            Ok(Flow::Normal)
         }
     }
 
     fn visit_while_stmt(&mut self, stmt: &stmt::While) -> Self::Output {
+        let label = stmt.label.as_ref().map(|label| &label.lexeme);
         let mut condition_result;
         while {
             condition_result = self.evaluate(&stmt.condition)?; 
             self.is_truthy(&condition_result)
         } { 
-            if let Some(val) = self.execute(&stmt.body)? {
-                return Ok(Some(val))
+            match self.execute(&stmt.body)? {
+                Flow::Break(target) if target.is_none() || target.as_ref() == label => break,
+                Flow::Continue(target) if target.is_none() || target.as_ref() == label => {},
+                Flow::Normal => {},
+                flow => return Ok(flow),
+            }
+            if let Some(increment) = &stmt.increment {
+                self.evaluate(increment)?;
             }
         }
-        Ok(None)
+        Ok(Flow::Normal)
     }
 
     fn visit_func_stmt(&mut self, stmt: &stmt::Func) -> Self::Output {
@@ -368,17 +581,71 @@ impl StmtVisitor for Interpreter {
         let func = Callable::new_lax_fn(stmt.clone(), env, false);
         let value = Arc::new(RwLock::new(Value::Callable(func)));
         self.environment.lock().unwrap().define(name, value);
-        Ok(None)
+        Ok(Flow::Normal)
     }
     
     fn visit_return_stmt(&mut self, stmt: &stmt::Return) -> Self::Output {
         let value = match &stmt.value {
-            Some(value) => Some(self.evaluate(value)?),
-            None => None
+            Some(value) => self.evaluate(value)?,
+            None => Arc::new(RwLock::new(Value::None)),
         };
-        Ok(value)
+        Ok(Flow::Return(value))
     }  
 
+    fn visit_break_stmt(&mut self, stmt: &stmt::Break) -> Self::Output {
+        let label = stmt.label.as_ref().map(|label| Rc::clone(&label.lexeme));
+        Ok(Flow::Break(label))
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &stmt::Continue) -> Self::Output {
+        let label = stmt.label.as_ref().map(|label| Rc::clone(&label.lexeme));
+        Ok(Flow::Continue(label))
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &stmt::Throw) -> Self::Output {
+        let value = self.evaluate(&stmt.value)?.read().unwrap().clone();
+        Err(RuntimeError::thrown(value, stmt.keyword.span.clone()))
+    }
+
+    //a finally block that returns, breaks or throws replaces how the try ended
+    fn visit_try_stmt(&mut self, stmt: &stmt::Try) -> Self::Output {
+        let env = Environment::new_wrapped(Some(Arc::clone(&self.environment)));
+        let mut result = self.execute_block(&stmt.body, env);
+
+        if let (Err(error), Some(catch)) = (&result, &stmt.catch) {
+            let env = Environment::new_wrapped(Some(Arc::clone(&self.environment)));
+            let value = Arc::new(RwLock::new(error.value()));
+            env.lock().unwrap().define(catch.name.lexeme.to_string(), value);
+            result = self.execute_block(&catch.body, env);
+        }
+
+        if let Some(finally) = &stmt.finally {
+            let env = Environment::new_wrapped(Some(Arc::clone(&self.environment)));
+            match self.execute_block(finally, env)? {
+                Flow::Normal => {},
+                flow => return Ok(flow),
+            }
+        }
+        result
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &stmt::Import) -> Self::Output {
+        let module = self.module(&stmt.path)?;
+        let mut env = self.environment.lock().unwrap();
+        if let Some(alias) = &stmt.alias {
+            let value = Value::Module(Arc::clone(&module));
+            env.define(alias.lexeme.to_string(), Arc::new(RwLock::new(value)));
+        }
+        for name in &stmt.names {
+            env.define(name.lexeme.to_string(), module.get(Rc::clone(name))?);
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &stmt::Export) -> Self::Output {
+        self.execute(&stmt.declaration)
+    }
+
     fn visit_class_stmt(&mut self, stmt: &stmt::Class) -> Self::Output {
         let mut superclass = None;
         if let Some(expr) = &stmt.superclass {
@@ -429,6 +696,6 @@ impl StmtVisitor for Interpreter {
         }
 
         self.environment.lock().unwrap().assign(stmt.token.clone(), value)?;
-        Ok(None)
+        Ok(Flow::Normal)
     }
 }
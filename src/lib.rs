@@ -0,0 +1,385 @@
+#![allow(unused_parens)]
+#![allow(clippy::arc_with_non_send_sync)]
+
+use std::{
+    env,
+    io::{self, Write},
+    sync::{Arc, RwLock},
+    path::{Path, PathBuf},
+    rc::Rc,
+    fs,
+};
+
+use rustyline::{Editor, error::ReadlineError, history::DefaultHistory};
+
+use scanner::Scanner;
+use ast::{parser::Parser, printer::AstPrinter, stmt::Stmt};
+use repl::{Repl, LaxHelper};
+use formatter::Formatter;
+use optimizer::Optimizer;
+use resolver::Resolver;
+use checker::Checker;
+use vm::{Compiler, Vm};
+use error::{ResolveError, IoError};
+use module::{Module, module_path};
+use token::Token;
+
+pub use error::{ErrorStatus, ErrorFormat, LaxError, RuntimeError};
+pub use interpreter::Interpreter;
+pub use token::Value;
+pub use ast::printer::AstFormat;
+pub use callables::Arity;
+pub use output::SharedBuffer;
+
+pub mod error;
+pub mod scanner;
+pub mod token;
+pub mod ast;
+pub mod optimizer;
+pub mod resolver;
+pub mod checker;
+pub mod interpreter;
+pub mod environment;
+pub mod callables;
+pub mod output;
+pub mod json;
+pub mod module;
+pub mod vm;
+pub mod repl;
+pub mod formatter;
+
+#[cfg(test)]
+mod test;
+
+/// Which engine executes a program once it has been parsed and resolved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Tree,
+    Vm,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "tree" => Ok(Backend::Tree),
+            "vm" => Ok(Backend::Vm),
+            _ => Err(format!("Unknown backend '{}', expected 'vm' or 'tree'.", name)),
+        }
+    }
+}
+
+/// An interpreter session. Globals defined by one call to `eval` or
+/// `run_file` stay visible to the next, whichever backend runs them.
+pub struct Lax {
+    interpreter: Interpreter,
+    status: ErrorStatus,
+    backend: Backend,
+    loading: Vec<(PathBuf, String)>, //files being loaded, to detect import cycles
+}
+
+impl Lax {
+    pub fn new() -> Self {
+        Lax {
+            interpreter: Interpreter::new(),
+            status: ErrorStatus::new(),
+            backend: Backend::Tree,
+            loading: vec!(),
+        }
+    }
+
+    /// Creates a session that sends `print` output to `stdout` and
+    /// diagnostics to `stderr` instead of the process streams.
+    pub fn with_output(stdout: Box<dyn Write>, stderr: Box<dyn Write>) -> Self {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(stdout);
+        Lax {
+            interpreter,
+            status: ErrorStatus::with_output(stderr),
+            backend: Backend::Tree,
+            loading: vec!(),
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// Chooses between rendered diagnostics and one JSON object per line.
+    pub fn set_error_format(&mut self, format: ErrorFormat) {
+        self.status.set_format(format);
+    }
+
+    /// Forgets every global and loaded module, as if the session had just
+    /// been created. Natives added with `define_native` are kept.
+    pub fn reset(&mut self) {
+        self.interpreter.reset();
+    }
+
+    /// Reports a file that couldn't be read or written, in the session's
+    /// error format like any other diagnostic, and returns the error.
+    pub fn io_error(&mut self, file: &str, action: &str, error: io::Error) -> LaxError {
+        self.status.report_io_error(IoError::new(file, action, &error));
+        LaxError::Io(error)
+    }
+
+    pub fn interpreter(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+
+    /// Registers a Rust closure as a global Lax function. Errors returned by
+    /// the closure are raised as Lax runtime errors at the call site.
+    pub fn define_native<F>(&mut self, name: &str, arity: Arity, func: F)
+    where F: Fn(&mut Interpreter, Vec<Arc<RwLock<Value>>>) -> Result<Value, RuntimeError> + 'static {
+        self.interpreter.define_native(name, arity, func)
+    }
+
+    /// Runs a script. Modules it imports are found relative to its directory.
+    pub fn run_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), LaxError> {
+        let file = path.as_ref().display().to_string();
+        let source = fs::read_to_string(&path)
+            .map_err(|error| self.io_error(&file, "read", error))?;
+        let canonical = fs::canonicalize(&path)
+            .map_err(|error| self.io_error(&file, "read", error))?;
+        self.loading.push((canonical, file.clone()));
+        let result = self.run(&source, &file, true);
+        self.loading.pop();
+        result?;
+        Ok(())
+    }
+
+    /// Reads statements from the terminal until `:quit` or end of input.
+    /// Input is buffered until its brackets balance, and the value of an
+    /// expression statement is echoed unless it is nil. Lines can be edited,
+    /// tab-completed and recalled from history, which is kept in
+    /// `~/.lax_history`.
+    pub fn run_prompt(&mut self) {
+        let mut editor: Editor<LaxHelper, DefaultHistory> = match Editor::new() {
+            Ok(editor) => editor,
+            Err(error) => {
+                eprintln!("Error starting line editor: {}", error);
+                return
+            },
+        };
+        let history = env::var_os("HOME").map(|home| Path::new(&home).join(".lax_history"));
+        if let Some(history) = &history {
+            //there is no history on the first run
+            let _ = editor.load_history(history);
+        }
+
+        let mut repl = Repl::new();
+        loop {
+            let globals = Arc::clone(&self.interpreter.globals);
+            editor.set_helper(Some(LaxHelper { globals }));
+            let line = match editor.readline(repl.prompt()) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => {
+                    repl.clear();
+                    continue
+                },
+                Err(ReadlineError::Eof) => break,
+                Err(error) => {
+                    eprintln!("Error reading line: {}", error);
+                    break
+                },
+            };
+            if !line.trim().is_empty() {
+                let _ = editor.add_history_entry(line.as_str());
+            }
+            if !repl.feed(self, &line) {
+                break
+            }
+        }
+
+        if let Some(history) = &history {
+            if let Err(error) = editor.save_history(history) {
+                eprintln!("Error saving history: {}", error);
+            }
+        }
+    }
+
+    /// Re-prints `source` in the canonical style, keeping its comments.
+    /// Source that doesn't parse is reported rather than formatted.
+    pub fn format(&mut self, source: &str, file: &str) -> Result<String, LaxError> {
+        self.status.had_compile_error = false;
+        let file = self.status.add_source(file, source);
+        let mut scanner = Scanner::new(&mut self.status, source.to_string(), file);
+        let tokens = scanner.scan_tokens();
+        let trivia = scanner.trivia();
+
+        Parser::new(&mut self.status, tokens.clone()).parse();
+        if self.status.had_compile_error {return Err(LaxError::Compile)}
+        Ok(Formatter::format(&tokens, &trivia))
+    }
+
+    /// Writes the tokens `source` scans into to the session's output, one
+    /// per line. Scan errors are reported after the tokens that did scan.
+    pub fn dump_tokens(&mut self, source: &str, file: &str) -> Result<(), LaxError> {
+        self.status.had_compile_error = false;
+        let file = self.status.add_source(file, source);
+        let tokens = Scanner::new(&mut self.status, source.to_string(), file).scan_tokens();
+        let output = self.interpreter.output();
+        for token in tokens {
+            let _ = writeln!(output, "{}", token.describe());
+        }
+        if self.status.had_compile_error {return Err(LaxError::Compile)}
+        Ok(())
+    }
+
+    /// Writes the syntax tree `source` parses into to the session's output.
+    /// Each variable, `this` and `super` is labeled with its expression id
+    /// and the scope depth the resolver found it at. When the source doesn't
+    /// parse, the statements that did are written without depths.
+    pub fn dump_ast(&mut self, source: &str, file: &str, format: AstFormat
+        ) -> Result<(), LaxError> {
+        self.status.had_compile_error = false;
+        let file = self.status.add_source(file, source);
+        let tokens = Scanner::new(&mut self.status, source.to_string(), file).scan_tokens();
+        let stmts = Parser::new(&mut self.status, tokens).parse();
+        let nodes = match self.status.had_compile_error {
+            true => AstPrinter::nodes(&stmts),
+            false => {
+                Resolver::new(&mut self.status, &mut self.interpreter).resolve(&stmts);
+                AstPrinter::resolved(&stmts, self.interpreter.locals())
+            },
+        };
+        let dump = AstPrinter::render(&nodes, format);
+        let _ = write!(self.interpreter.output(), "{}", dump);
+        if self.status.had_compile_error {return Err(LaxError::Compile)}
+        Ok(())
+    }
+
+    /// Runs `source` to completion and returns the value of its last
+    /// statement when that statement is an expression, otherwise nil.
+    pub fn eval(&mut self, source: &str) -> Result<Value, LaxError> {
+        self.run(source, "<eval>", false)
+    }
+
+    //`file` names the source in diagnostics. `program` is false for eval and
+    //REPL input, which later input may still build on
+    fn run(&mut self, source: &str, file: &str, program: bool) -> Result<Value, LaxError> {
+        self.status.had_compile_error = false;
+        self.status.had_runtime_error = false;
+        let stmts = self.parse(source, file)?;
+        self.execute(&stmts, program)
+    }
+
+    //parses a file and loads the modules it imports
+    fn parse(&mut self, source: &str, file: &str) -> Result<Vec<Stmt>, LaxError> {
+        let stmts = self.syntax(source, file)?;
+
+        for stmt in &stmts {
+            let import = match stmt {
+                Stmt::Import(import) => import,
+                _ => continue,
+            };
+            let module = self.load_module(&import.path)?;
+            for name in &import.names {
+                if !module.exports.contains(name.lexeme.as_str()) {
+                    let message = module.not_exported(&name.lexeme);
+                    self.status.report_compile_error(ResolveError::new(Rc::clone(name), &message));
+                }
+            }
+        }
+        if self.status.had_compile_error {return Err(LaxError::Compile)}
+        Ok(stmts)
+    }
+
+    //scans and parses without touching any other file
+    fn syntax(&mut self, source: &str, file: &str) -> Result<Vec<Stmt>, LaxError> {
+        let file = self.status.add_source(file, source);
+        let mut scanner = Scanner::new(&mut self.status, source.to_string(), file);
+        let tokens = scanner.scan_tokens();
+
+        let mut parser = Parser::new(&mut self.status, tokens);
+        let stmts = parser.parse();
+        if self.status.had_compile_error {return Err(LaxError::Compile)}
+        Ok(stmts)
+    }
+
+    //each module runs once, in globals of its own, and is then cached
+    fn load_module(&mut self, path: &Rc<Token>) -> Result<Arc<Module>, LaxError> {
+        let file = module_path(path);
+        let name = file.display().to_string();
+        let canonical = match fs::canonicalize(&file) {
+            Ok(canonical) => canonical,
+            Err(_) => {
+                let message = format!("Can't find module '{}'.", name);
+                return Err(self.import_error(path, &message))
+            },
+        };
+        if let Some(module) = self.interpreter.loaded_module(&canonical) {
+            return Ok(module)
+        }
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == canonical) {
+            let mut cycle: Vec<&str> = self.loading[start..].iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            cycle.push(&name);
+            let message = format!("Import cycle: {}.", cycle.join(" -> "));
+            return Err(self.import_error(path, &message))
+        }
+
+        let source = fs::read_to_string(&canonical)
+            .map_err(|error| self.io_error(&name, "read", error))?;
+        self.loading.push((canonical.clone(), name.clone()));
+        let result = self.run_module(&source, name);
+        self.loading.pop();
+
+        let module = Arc::new(result?);
+        self.interpreter.add_module(canonical, Arc::clone(&module));
+        Ok(module)
+    }
+
+    fn run_module(&mut self, source: &str, name: String) -> Result<Module, LaxError> {
+        let stmts = self.parse(source, &name)?;
+        let globals = self.interpreter.module_globals();
+        let importer = self.interpreter.replace_globals(Arc::clone(&globals));
+        let result = self.execute(&stmts, true);
+        self.interpreter.replace_globals(importer);
+        result?;
+        Ok(Module::new(name, globals, &stmts))
+    }
+
+    fn import_error(&mut self, path: &Rc<Token>, message: &str) -> LaxError {
+        self.status.report_compile_error(ResolveError::new(Rc::clone(path), message));
+        LaxError::Compile
+    }
+
+    fn execute(&mut self, stmts: &[Stmt], program: bool) -> Result<Value, LaxError> {
+        let mut resolver = Resolver::new(&mut self.status, &mut self.interpreter);
+        if program {
+            resolver = resolver.with_unused_functions();
+        }
+        resolver.resolve(stmts);
+        if self.status.had_compile_error {return Err(LaxError::Compile)}
+        Checker::new(&mut self.status).check(stmts);
+        if self.status.had_compile_error {return Err(LaxError::Compile)}
+        //ids survive optimizing, so the depths resolved above still apply
+        let stmts = &Optimizer::optimize(stmts);
+
+        let result = match self.backend {
+            Backend::Tree => self.interpreter.interpret(stmts)
+                .map(|value| value.read().unwrap().clone()),
+            Backend::Vm => {
+                let function = Compiler::new(&mut self.status).compile(stmts);
+                if self.status.had_compile_error {return Err(LaxError::Compile)}
+                Vm::new(&mut self.interpreter).interpret(function)
+            },
+        };
+        match result {
+            Ok(value) => Ok(value),
+            Err(error) => {
+                self.status.report_runtime_error(&error);
+                Err(LaxError::Runtime(error))
+            },
+        }
+    }
+}
+
+impl Default for Lax {
+    fn default() -> Self {
+        Self::new()
+    }
+}
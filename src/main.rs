@@ -1,109 +1,144 @@
-#![allow(unused_parens)]
-
 use std::{
-    io::{self, Write},
     env,
+    fs,
+    io::{self, Read},
     process,
-    fs, 
+    str::FromStr,
 };
 
-use scanner::Scanner;
-use error::ErrorStatus;
-use interpreter::Interpreter;
-use ast::parser::Parser;
-use resolver::Resolver;
+use first_compiler::{Lax, Backend, ErrorFormat, AstFormat};
 
-mod error;
-mod scanner;
-mod token;
-mod ast;
-mod resolver;
-mod interpreter;
-mod environment;
-mod callables;
-mod test;
-
-pub mod test_file {
-    use super::Lax;
-    pub fn test_file(path: &str) {
-        let mut session = Lax::new();
-        session.run_file(path.to_string());
-    }
-}
+const USAGE: &str = concat!(
+    "Usage: first-compiler [--backend=vm|tree] [--error-format=human|json] [script]\n",
+    "       first-compiler [--dump-tokens] [--dump-ast[=tree|sexpr|json]] script\n",
+    "       first-compiler fmt [--check] [--error-format=human|json] [file...]",
+);
 
 fn main() {
     let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "fmt") {
+        process::exit(fmt(&args[1..]));
+    }
+
     let mut session = Lax::new();
-    match args.len() {
+    let mut paths = vec!();
+    let mut dump_tokens = false;
+    let mut dump_ast = None;
+    for arg in args {
+        if arg == "--dump-tokens" {
+            dump_tokens = true;
+        } else if arg == "--dump-ast" {
+            dump_ast = Some(AstFormat::Tree);
+        } else if let Some(name) = arg.strip_prefix("--dump-ast=") {
+            dump_ast = Some(parse_flag::<AstFormat>(name));
+        } else if let Some(name) = arg.strip_prefix("--backend=") {
+            session.set_backend(parse_flag::<Backend>(name));
+        } else if let Some(name) = arg.strip_prefix("--error-format=") {
+            session.set_error_format(parse_flag::<ErrorFormat>(name));
+        } else {
+            paths.push(arg);
+        }
+    }
+
+    if dump_tokens || dump_ast.is_some() {
+        if paths.len() != 1 {
+            eprintln!("Dumping needs exactly one script\n{}", USAGE);
+            process::exit(64);
+        }
+        process::exit(dump(&mut session, &paths[0], dump_tokens, dump_ast));
+    }
+
+    match paths.len() {
         0 => session.run_prompt(),
-        1 => session.run_file(args[0].to_string()),
+        1 => if let Err(error) = session.run_file(&paths[0]) {
+            process::exit(error.exit_code());
+        },
         num_args => {
-            eprintln!("Expected 1 argument but {} were given", num_args);
+            eprintln!("Expected 1 argument but {} were given\n{}", num_args, USAGE);
             process::exit(64);
         },
     };
 }
 
-
-struct Lax {
-    interpreter: Interpreter,
-    status: ErrorStatus,
+//writes the tokens and then the syntax tree instead of running the script
+fn dump(session: &mut Lax, path: &str, tokens: bool, ast: Option<AstFormat>) -> i32 {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => return session.io_error(path, "read", error).exit_code(),
+    };
+    let mut result = Ok(());
+    if tokens {
+        result = session.dump_tokens(&source, path);
+    }
+    if let (Some(format), Ok(())) = (ast, &result) {
+        result = session.dump_ast(&source, path, format);
+    }
+    match result {
+        Ok(()) => 0,
+        Err(error) => error.exit_code(),
+    }
 }
 
-impl Lax {
-    pub fn new() -> Self {
-        Lax {
-            interpreter: Interpreter::new(),
-            status: ErrorStatus::new() 
+//rewrites each file in place, or with --check only lists the files that
+//aren't formatted. with no files it formats stdin to stdout
+fn fmt(args: &[String]) -> i32 {
+    let mut session = Lax::new();
+    let mut check = false;
+    let mut paths = vec!();
+    for arg in args {
+        if arg == "--check" {
+            check = true;
+        } else if let Some(name) = arg.strip_prefix("--error-format=") {
+            session.set_error_format(parse_flag::<ErrorFormat>(name));
+        } else {
+            paths.push(arg.as_str());
         }
     }
-    pub fn run_file(&mut self, path: String) {
-        let source = match fs::read_to_string(path) {
-            Ok(file) => file,
-            Err(_) => {
-                eprintln!("File not found in current directory.");
-                process::exit(1)
-            },
-        };
-        self.run(source);
-        if self.status.had_compile_error {process::exit(65)}
-        if self.status.had_runtime_error {process::exit(70)}
-    }
 
-    pub fn run_prompt(&mut self) {
-        let mut status = ErrorStatus::new();
-        loop {
-            print!("> ");
-            io::stdout().flush().unwrap();
-
-            let mut line = String::new();
-            if let Err(error) = io::stdin().read_line(&mut line) {
-                eprintln!("Error reading line: {:?}", error);
-            }
-            line = line.trim().to_string();
-            
-            self.run(line);
-            status.had_compile_error = false;
+    if paths.is_empty() {
+        let mut source = String::new();
+        if let Err(error) = io::stdin().read_to_string(&mut source) {
+            return session.io_error("<stdin>", "read", error).exit_code()
+        }
+        return match session.format(&source, "<stdin>") {
+            Ok(formatted) if check && formatted != source => {
+                eprintln!("<stdin> is not formatted");
+                1
+            },
+            Ok(_) if check => 0,
+            Ok(formatted) => {print!("{}", formatted); 0},
+            Err(error) => error.exit_code(),
         }
     }
 
-    fn run(&mut self, source: String) {
-        let mut  scanner = Scanner::new(&mut self.status, source);
-        let tokens = scanner.scan_tokens();
-
-        let mut parser = Parser::new(&mut self.status, tokens);
-        let stmts = parser.parse();
-
-        if self.status.had_compile_error {return};
-
-        let mut resolver = Resolver::new(&mut self.status, &mut self.interpreter);
-        resolver.resolve(&stmts);
-        if self.status.had_runtime_error {return}
-
-        if let Err(error) = self.interpreter.interpret(&stmts) {
-             self.status.report_runtime_error(error); 
+    let mut code = 0;
+    for path in paths {
+        let result = fs::read_to_string(path)
+            .map_err(|error| session.io_error(path, "read", error))
+            .and_then(|source| Ok((session.format(&source, path)?, source)));
+        let (formatted, source) = match result {
+            Ok(result) => result,
+            Err(error) => {
+                code = code.max(error.exit_code());
+                continue
+            },
+        };
+        if formatted == source {
+            continue
+        }
+        if check {
+            eprintln!("{} is not formatted", path);
+            code = code.max(1);
+        } else if let Err(error) = fs::write(path, formatted) {
+            code = code.max(session.io_error(path, "write", error).exit_code());
         }
     }
+    code
 }
 
-
+fn parse_flag<T: FromStr<Err = String>>(value: &str) -> T {
+    value.parse().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        process::exit(64);
+    })
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use crate::{
+    ast::stmt::Stmt,
+    environment::Environment,
+    error::RuntimeError,
+    token::{Token, Value},
+};
+
+//a file evaluated once in globals of its own. files that import it can
+//only reach the names it exports
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    pub globals: Arc<Mutex<Environment>>,
+    pub exports: HashSet<String>,
+}
+
+impl Module {
+    pub fn new(name: String, globals: Arc<Mutex<Environment>>, stmts: &[Stmt]) -> Self {
+        let exports = stmts.iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::Export(export) => Some(declared_name(&export.declaration)),
+                _ => None,
+            })
+            .collect();
+        Module { name, globals, exports }
+    }
+
+    pub fn get(&self, token: Rc<Token>) -> Result<Arc<RwLock<Value>>, RuntimeError> {
+        match self.lookup(&token.lexeme) {
+            Some(value) => Ok(value),
+            None => {
+                let message = self.not_exported(&token.lexeme);
+                Err(RuntimeError::new(token, &message))
+            },
+        }
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Arc<RwLock<Value>>> {
+        if !self.exports.contains(name) {
+            return None
+        }
+        self.globals.lock().unwrap().values.get(name).cloned()
+    }
+
+    pub fn not_exported(&self, name: &str) -> String {
+        format!("Module '{}' does not export '{}'.", self.name, name)
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+fn declared_name(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Let(stmt) => stmt.token.lexeme.to_string(),
+        Stmt::Func(stmt) => stmt.token.lexeme.to_string(),
+        Stmt::Class(stmt) => stmt.token.lexeme.to_string(),
+        _ => unreachable!("the parser only exports declarations"),
+    }
+}
+
+//module paths are relative to the directory of the file importing them
+pub fn module_path(path: &Token) -> PathBuf {
+    let importer = Path::new(path.span.file.as_str());
+    let dir = importer.parent().unwrap_or(Path::new(""));
+    match &*path.literal.read().unwrap() {
+        Value::String(path) => dir.join(path),
+        _ => unreachable!("the parser only accepts string module paths"),
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    rc::Rc,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    ast::{
+        expr::{self, Expr, AcceptExprVisitor, ExprVisitor},
+        stmt::{self, Stmt, AcceptStmtVisitor, StmtVisitor},
+    },
+    token::{Span, TokenType, Value},
+};
+
+//rewrites the tree so work that can be done once isn't redone every time the
+//code runs. operators on literals are folded, branches that can never be
+//taken are dropped along with statements after a jump, and `!!x` becomes x
+//where only its truthiness matters.
+//it runs after the tree is resolved and checked, so errors in code it drops
+//are still reported. ids are kept and no scope is added or removed around a
+//declaration, so the depths the resolver recorded still hold.
+//anything that would fail at runtime is left alone so the error is still
+//raised, from the same token, when the code runs
+pub struct Optimizer;
+
+impl Optimizer {
+    pub fn optimize(stmts: &[Stmt]) -> Vec<Stmt> {
+        Optimizer.stmts(stmts)
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Vec<Stmt> {
+        let mut optimized = vec!();
+        for stmt in stmts {
+            optimized.push(stmt.accept(self));
+            if is_jump(stmt) {
+                break
+            }
+        }
+        optimized
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Box<Stmt> {
+        Box::new(stmt.accept(self))
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Box<Expr> {
+        Box::new(expr.accept(self))
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Vec<Expr> {
+        exprs.iter().map(|expr| expr.accept(self)).collect()
+    }
+
+    fn func(&mut self, func: &stmt::Func) -> stmt::Func {
+        stmt::Func { body: self.stmts(&func.body), ..func.clone() }
+    }
+
+    //an expression whose value is only tested for truthiness, like a
+    //condition or the operand of `!`
+    fn condition(&mut self, expr: &Expr) -> Expr {
+        if let Some(operand) = negated(expr).and_then(negated) {
+            return self.condition(operand)
+        }
+        match expr {
+            Expr::Logical(logical) => self.logical(logical, Self::condition),
+            expr => expr.accept(self),
+        }
+    }
+
+    //`and` and `or` produce one of their operands, so a literal on the left
+    //decides which
+    fn logical(&mut self, expr: &expr::Logical, operand: fn(&mut Self, &Expr) -> Expr) -> Expr {
+        let left = operand(self, &expr.left);
+        let truthy = match constant(&left) {
+            Some(value) => value.is_truthy(),
+            None => return Expr::Logical(expr::Logical {
+                id: expr.id,
+                left: Box::new(left),
+                operator: Rc::clone(&expr.operator),
+                right: Box::new(operand(self, &expr.right)),
+            }),
+        };
+        match (&expr.operator.token_type, truthy) {
+            (TokenType::Or, true) | (TokenType::And, false) => left,
+            _ => operand(self, &expr.right),
+        }
+    }
+}
+
+//a statement that never lets the one after it run
+fn is_jump(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Return(_) | Stmt::Break(_) | Stmt::Continue(_) | Stmt::Throw(_))
+}
+
+fn constant(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Literal(literal) => Some(literal.value.read().unwrap().clone()),
+        _ => None,
+    }
+}
+
+fn literal(value: Value, span: Span) -> Expr {
+    Expr::new_literal(Arc::new(RwLock::new(value)), span)
+}
+
+//the operand of `!x`, seeing through parentheses
+fn negated(expr: &Expr) -> Option<&Expr> {
+    match expr {
+        Expr::Grouping(grouping) => negated(&grouping.expr),
+        Expr::Unary(unary) if unary.operator.token_type == TokenType::Bang => Some(&unary.right),
+        _ => None,
+    }
+}
+
+//an if or loop that was removed leaves an empty block behind, so the last
+//statement of a script is still the one whose value gets echoed
+fn removed(span: &Span) -> Stmt {
+    Stmt::new_block(vec!(), span.clone())
+}
+
+impl ExprVisitor for Optimizer {
+    type Output = Expr;
+
+    fn visit_binary_expr(&mut self, expr: &expr::Binary) -> Expr {
+        let (left, right) = (self.expr(&expr.left), self.expr(&expr.right));
+        if let (Some(a), Some(b)) = (constant(&left), constant(&right)) {
+            if let Ok(value) = a.binary(&expr.operator.token_type, &b) {
+                return literal(value, left.span().to(&right.span()))
+            }
+        }
+        Expr::Binary(expr::Binary {
+            id: expr.id,
+            left,
+            operator: Rc::clone(&expr.operator),
+            right,
+        })
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &expr::Grouping) -> Expr {
+        let inner = self.expr(&expr.expr);
+        match constant(&inner) {
+            Some(value) => literal(value, expr.span.clone()),
+            None => Expr::Grouping(expr::Grouping {
+                id: expr.id,
+                expr: inner,
+                span: expr.span.clone(),
+            }),
+        }
+    }
+
+    fn visit_literal_expr(&mut self, expr: &expr::Literal) -> Expr {
+        Expr::Literal(expr.clone())
+    }
+
+    fn visit_unary_expr(&mut self, expr: &expr::Unary) -> Expr {
+        let right = match expr.operator.token_type {
+            TokenType::Bang => Box::new(self.condition(&expr.right)),
+            _ => self.expr(&expr.right),
+        };
+        if let Some(value) = constant(&right) {
+            if let Ok(value) = value.unary(&expr.operator.token_type) {
+                return literal(value, expr.operator.span.to(&right.span()))
+            }
+        }
+        Expr::Unary(expr::Unary {
+            id: expr.id,
+            operator: Rc::clone(&expr.operator),
+            right,
+        })
+    }
+
+    fn visit_var_expr(&mut self, expr: &expr::Var) -> Expr {
+        Expr::Var(expr.clone())
+    }
+
+    fn visit_assign_expr(&mut self, expr: &expr::Assign) -> Expr {
+        Expr::Assign(expr::Assign { value: self.expr(&expr.value), ..expr.clone() })
+    }
+
+    fn visit_logical_expr(&mut self, expr: &expr::Logical) -> Expr {
+        self.logical(expr, |optimizer, operand| operand.accept(optimizer))
+    }
+
+    fn visit_call_expr(&mut self, expr: &expr::Call) -> Expr {
+        Expr::Call(expr::Call {
+            id: expr.id,
+            callee: self.expr(&expr.callee),
+            paren: Rc::clone(&expr.paren),
+            args: self.exprs(&expr.args),
+        })
+    }
+
+    fn visit_get_expr(&mut self, expr: &expr::Get) -> Expr {
+        Expr::Get(expr::Get { object: self.expr(&expr.object), ..expr.clone() })
+    }
+
+    fn visit_set_expr(&mut self, expr: &expr::Set) -> Expr {
+        Expr::Set(expr::Set {
+            object: self.expr(&expr.object),
+            value: self.expr(&expr.value),
+            ..expr.clone()
+        })
+    }
+
+    fn visit_this_expr(&mut self, expr: &expr::This) -> Expr {
+        Expr::This(expr.clone())
+    }
+
+    fn visit_super_expr(&mut self, expr: &expr::Super) -> Expr {
+        Expr::Super(expr.clone())
+    }
+
+    fn visit_list_expr(&mut self, expr: &expr::List) -> Expr {
+        Expr::List(expr::List { elements: self.exprs(&expr.elements), ..expr.clone() })
+    }
+
+    fn visit_index_expr(&mut self, expr: &expr::Index) -> Expr {
+        Expr::Index(expr::Index {
+            object: self.expr(&expr.object),
+            index: self.expr(&expr.index),
+            ..expr.clone()
+        })
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &expr::SetIndex) -> Expr {
+        Expr::SetIndex(expr::SetIndex {
+            object: self.expr(&expr.object),
+            index: self.expr(&expr.index),
+            value: self.expr(&expr.value),
+            ..expr.clone()
+        })
+    }
+
+    fn visit_map_expr(&mut self, expr: &expr::Map) -> Expr {
+        let entries = expr.entries.iter()
+            .map(|(key, value)| (key.accept(self), value.accept(self)))
+            .collect();
+        Expr::Map(expr::Map { entries, ..expr.clone() })
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &expr::Lambda) -> Expr {
+        Expr::Lambda(expr::Lambda { func: self.func(&expr.func), ..expr.clone() })
+    }
+
+    //constant parts next to each other are joined into one, so a string
+    //whose parts are all constant becomes a literal
+    fn visit_interpolation_expr(&mut self, expr: &expr::Interpolation) -> Expr {
+        let mut parts: Vec<Expr> = vec!();
+        for part in &expr.parts {
+            let part = part.accept(self);
+            let joined = match (parts.last().and_then(constant), constant(&part)) {
+                (Some(before), Some(value)) => format!("{}{}", before, value),
+                _ => {
+                    parts.push(part);
+                    continue
+                },
+            };
+            let span = parts.pop().unwrap().span().to(&part.span());
+            parts.push(literal(Value::String(joined), span));
+        }
+        //a lone constant still has to come out as a string, `"${1}"` isn't 1
+        if let [part] = parts.as_slice() {
+            if let Some(value) = constant(part) {
+                return literal(Value::String(value.to_string()), expr.span.clone())
+            }
+        }
+        Expr::Interpolation(expr::Interpolation { parts, ..expr.clone() })
+    }
+}
+
+impl StmtVisitor for Optimizer {
+    type Output = Stmt;
+
+    fn visit_expr_stmt(&mut self, stmt: &stmt::StmtExpr) -> Stmt {
+        Stmt::new_stmt_expr(stmt.expr.accept(self))
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &stmt::Print) -> Stmt {
+        Stmt::new_print(stmt.expr.accept(self), stmt.span.clone())
+    }
+
+    fn visit_let_stmt(&mut self, stmt: &stmt::Let) -> Stmt {
+        let initializer = stmt.initializer.as_ref().map(|expr| expr.accept(self));
+        Stmt::Let(stmt::Let { initializer, ..stmt.clone() })
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &stmt::Block) -> Stmt {
+        Stmt::new_block(self.stmts(&stmt.stmts), stmt.span.clone())
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &stmt::If) -> Stmt {
+        let condition = self.condition(&stmt.condition);
+        match constant(&condition).map(|value| value.is_truthy()) {
+            Some(true) => stmt.body.accept(self),
+            Some(false) => match &stmt.else_body {
+                Some(else_body) => else_body.accept(self),
+                None => removed(&stmt.span),
+            },
+            None => Stmt::If(stmt::If {
+                condition,
+                body: self.stmt(&stmt.body),
+                else_body: stmt.else_body.as_ref().map(|body| self.stmt(body)),
+                span: stmt.span.clone(),
+            }),
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &stmt::While) -> Stmt {
+        let condition = self.condition(&stmt.condition);
+        if let Some(false) = constant(&condition).map(|value| value.is_truthy()) {
+            return removed(&stmt.span)
+        }
+        Stmt::While(stmt::While {
+            condition,
+            body: self.stmt(&stmt.body),
+            increment: stmt.increment.as_ref().map(|expr| expr.accept(self)),
+            label: stmt.label.clone(),
+            span: stmt.span.clone(),
+        })
+    }
+
+    fn visit_func_stmt(&mut self, stmt: &stmt::Func) -> Stmt {
+        Stmt::Func(self.func(stmt))
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &stmt::Return) -> Stmt {
+        let value = stmt.value.as_ref().map(|expr| expr.accept(self));
+        Stmt::new_return(Rc::clone(&stmt.keyword), value)
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &stmt::Class) -> Stmt {
+        Stmt::Class(stmt::Class { methods: self.stmts(&stmt.methods), ..stmt.clone() })
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &stmt::Break) -> Stmt {
+        Stmt::Break(stmt.clone())
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &stmt::Continue) -> Stmt {
+        Stmt::Continue(stmt.clone())
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &stmt::Import) -> Stmt {
+        Stmt::Import(stmt.clone())
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &stmt::Export) -> Stmt {
+        Stmt::new_export(Rc::clone(&stmt.keyword), stmt.declaration.accept(self))
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &stmt::Throw) -> Stmt {
+        Stmt::new_throw(Rc::clone(&stmt.keyword), stmt.value.accept(self))
+    }
+
+    fn visit_try_stmt(&mut self, stmt: &stmt::Try) -> Stmt {
+        let catch = stmt.catch.as_ref().map(|catch| stmt::Catch {
+            name: Rc::clone(&catch.name),
+            body: self.stmts(&catch.body),
+        });
+        let finally = stmt.finally.as_ref().map(|finally| self.stmts(finally));
+        Stmt::new_try(Rc::clone(&stmt.keyword), self.stmts(&stmt.body), catch, finally)
+    }
+}
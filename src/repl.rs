@@ -0,0 +1,226 @@
+use std::{
+    io,
+    mem,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use rustyline::{
+    completion::Completer,
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::Validator,
+    Context,
+    Helper,
+};
+
+use crate::{
+    Lax,
+    LaxError,
+    ast::printer::AstPrinter,
+    callables::Callable,
+    environment::Environment,
+    error::ErrorStatus,
+    scanner::{Scanner, KEYWORDS},
+    token::{TokenType, Value},
+};
+
+const COMMANDS: &str = ":env, :load <file>, :reset, :ast <expr> and :quit";
+
+//collects lines until they form complete input and runs it. a line that
+//starts with `:` while nothing is buffered is a command instead
+pub struct Repl {
+    buffer: String,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Repl { buffer: String::new() }
+    }
+
+    pub fn prompt(&self) -> &'static str {
+        match self.buffer.is_empty() {
+            true => "> ",
+            false => "... ",
+        }
+    }
+
+    //drops a partly typed statement, as when the line is interrupted
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    //returns false once the session should end
+    pub fn feed(&mut self, lax: &mut Lax, line: &str) -> bool {
+        if self.buffer.is_empty() {
+            if let Some(command) = line.trim().strip_prefix(':') {
+                return self.command(lax, command)
+            }
+        }
+        self.buffer.push_str(line);
+        if !line.ends_with('\n') {
+            self.buffer.push('\n');
+        }
+        if !is_complete(&self.buffer) {
+            return true
+        }
+
+        let source = mem::take(&mut self.buffer);
+        //errors have already been reported, the prompt just keeps going.
+        //nil isn't echoed so calls made for their effects stay quiet
+        match lax.run(&source, "<repl>", false) {
+            Ok(Value::None) | Err(_) => {},
+            Ok(value) => {let _ = lax.interpreter.print(&value);},
+        }
+        true
+    }
+
+    fn command(&mut self, lax: &mut Lax, command: &str) -> bool {
+        let (name, arg) = match command.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (command, ""),
+        };
+        match name {
+            "quit" => return false,
+            "env" => env(lax),
+            "reset" => lax.reset(),
+            "load" if arg.is_empty() => lax.status.report_message("Usage: :load <file>"),
+            "load" => {
+                //other errors have already been reported
+                if let Err(error @ LaxError::Io(_)) = lax.run_file(arg) {
+                    lax.status.report_message(&error.to_string());
+                }
+            },
+            "ast" if arg.is_empty() => lax.status.report_message("Usage: :ast <expr>"),
+            "ast" => ast(lax, arg),
+            _ => {
+                let message = format!("Unknown command ':{}'. Commands are {}.", name, COMMANDS);
+                lax.status.report_message(&message);
+            },
+        }
+        true
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//input is complete once every bracket it opens has been closed and it
+//doesn't end inside a string. too many closing brackets is left for the
+//parser to report
+fn is_complete(source: &str) -> bool {
+    let mut status = ErrorStatus::with_output(Box::new(io::sink()));
+    let file = Rc::new("<repl>".to_string());
+    let mut scanner = Scanner::new(&mut status, source.to_string(), file);
+    let tokens = scanner.scan_tokens();
+    if scanner.is_unterminated() {
+        return false
+    }
+    let depth = tokens.iter().fold(0, |depth, token| match token.token_type {
+        TokenType::OpenParen | TokenType::OpenBrace | TokenType::OpenBracket => depth + 1,
+        TokenType::CloseParen | TokenType::CloseBrace | TokenType::CloseBracket => depth - 1,
+        _ => depth,
+    });
+    depth <= 0
+}
+
+//completes the word that ends `line`. after a `.` the candidates are the
+//members of the value the names before it lead to, so `p.` lists p's fields
+//and methods. otherwise they are keywords and globals. returns where the
+//word starts along with the candidates
+pub fn complete(globals: &Arc<Mutex<Environment>>, line: &str) -> (usize, Vec<String>) {
+    let start = line.char_indices().rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '.')
+        .last()
+        .map_or(line.len(), |(i, _)| i);
+    let path = &line[start..];
+    let (candidates, word, start) = match path.rfind('.') {
+        Some(dot) => {
+            let candidates = match lookup(globals, &path[..dot]) {
+                Some(value) => value.members(),
+                None => vec!(),
+            };
+            (candidates, &path[dot + 1..], start + dot + 1)
+        },
+        None => {
+            let mut candidates: Vec<String> = KEYWORDS.iter().map(|(k, _)| k.to_string()).collect();
+            candidates.extend(globals.lock().unwrap().values.keys().cloned());
+            (candidates, path, start)
+        },
+    };
+    let mut candidates: Vec<String> = candidates.into_iter()
+        .filter(|candidate| candidate.starts_with(word))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+//follows a path like `a.b` through globals and fields without running code
+fn lookup(globals: &Arc<Mutex<Environment>>, path: &str) -> Option<Value> {
+    let mut names = path.split('.');
+    let global = globals.lock().unwrap().values.get(names.next()?).cloned()?;
+    let mut value = global.read().unwrap().clone();
+    for name in names {
+        value = value.field(name)?;
+    }
+    Some(value)
+}
+
+//hooks completion into the line editor. the globals are swapped whenever
+//the session is reset
+pub struct LaxHelper {
+    pub globals: Arc<Mutex<Environment>>,
+}
+
+impl Completer for LaxHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>
+        ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&self.globals, &line[..pos]))
+    }
+}
+
+impl Hinter for LaxHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LaxHelper {}
+
+impl Validator for LaxHelper {}
+
+impl Helper for LaxHelper {}
+
+//the globals the session has defined, sorted by name. natives are left out
+//since every session has them
+fn env(lax: &mut Lax) {
+    let globals = Arc::clone(&lax.interpreter.globals);
+    let mut lines: Vec<String> = globals.lock().unwrap().values.iter()
+        .filter_map(|(name, value)| match &*value.read().unwrap() {
+            Value::Callable(Callable::NativeFn(_)) => None,
+            value => Some(format!("{} = {}", name, value)),
+        })
+        .collect();
+    lines.sort();
+    let output = lax.interpreter.output();
+    for line in lines {
+        let _ = writeln!(output, "{}", line);
+    }
+}
+
+//the expression doesn't need its semicolon
+fn ast(lax: &mut Lax, source: &str) {
+    let mut source = source.to_string();
+    if !source.ends_with(';') && !source.ends_with('}') {
+        source.push(';');
+    }
+    lax.status.had_compile_error = false;
+    if let Ok(stmts) = lax.syntax(&source, "<repl>") {
+        let tree = AstPrinter::tree(&stmts);
+        let _ = write!(lax.interpreter.output(), "{}", tree);
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::hash_map::HashMap;
+use std::collections::{hash_map::HashMap, HashSet};
 use std::rc::Rc;
 use std::mem;
 
@@ -8,11 +8,69 @@ use crate::{
         expr::{self, ExprVisitor, Expr, AcceptExprVisitor},
         stmt::{self, StmtVisitor, Stmt, AcceptStmtVisitor, Func},
     },
-    token::Token,
-    error::{ErrorStatus, ParseError, RuntimeError},
+    token::{Token, Span},
+    error::{ErrorStatus, ResolveError, Note, Warning},
     callables::callable::FuncType,
 };
 
+#[derive(Clone, Copy, PartialEq)]
+enum LocalKind {
+    Variable,
+    Parameter,
+    Function,
+    Class,
+    Internal, //`this` and `super`, which the user never declares
+}
+
+struct Local {
+    defined: bool,
+    span: Span,
+    kind: LocalKind,
+    read: bool,
+    function: usize, //how many functions deep it was declared
+    loops: usize, //how many loops deep it was declared, within that function
+    captured: bool, //touched by a nested function, which may run at any time
+    unread_assignment: Option<Span>,
+}
+
+impl Local {
+    fn new(span: Span, kind: LocalKind, function: usize, loops: usize) -> Self {
+        Local {
+            defined: false,
+            span,
+            kind,
+            read: false,
+            function,
+            loops,
+            captured: false,
+            unread_assignment: None,
+        }
+    }
+
+    fn warning(&self, name: &str) -> Option<Warning> {
+        if name.starts_with('_') {
+            return None
+        }
+        let (code, kind) = match self.kind {
+            LocalKind::Variable => ("unused-variable", "variable"),
+            LocalKind::Parameter => ("unused-parameter", "parameter"),
+            LocalKind::Function => ("unused-function", "function"),
+            LocalKind::Class | LocalKind::Internal => return None,
+        };
+        if !self.read {
+            let message = format!("Unused {} '{}'.", kind, name);
+            return Some(Warning::new(code, self.span.clone(), &message))
+        }
+        match &self.unread_assignment {
+            Some(span) if !self.captured => {
+                let message = format!("Value assigned to '{}' is never read.", name);
+                Some(Warning::new("unused-assignment", span.clone(), &message))
+            },
+            _ => None,
+        }
+    }
+}
+
 enum ClassType {
     Class,
     SubClass,
@@ -21,9 +79,15 @@ enum ClassType {
 
 pub struct Resolver<'a> {
     interpreter: &'a mut Interpreter,
-    scopes: Vec<HashMap<String, bool>>, //bool represent weather the entry has been resolved yet.
+    scopes: Vec<HashMap<String, Local>>,
     func_type: FuncType,
     class_type: ClassType,
+    loops: Vec<Option<Rc<String>>>, //labels of the loops enclosing the current statement
+    functions: usize,
+    warnings: Vec<Warning>,
+    unused_functions: bool, //whether unused top level functions are reported
+    top_level_functions: Vec<Rc<Token>>, //declared at the top level and not exported
+    global_reads: HashSet<String>, //every global read, from any scope
     status: &'a mut ErrorStatus,
 }
 
@@ -35,17 +99,50 @@ impl<'a> Resolver<'a>  {
             scopes: vec!(),
             func_type: FuncType::None,
             class_type: ClassType::None,
+            loops: vec!(),
+            functions: 0,
+            warnings: vec!(),
+            unused_functions: false,
+            top_level_functions: vec!(),
+            global_reads: HashSet::new(),
             status
         } 
     }
 
-    pub fn resolve(&mut self, stmts: &Vec<Stmt>) {
-        self.begin_scope();
+    //also warns about top level functions nothing calls. Only worth it for a
+    //whole program, not for REPL input whose functions later input may call
+    pub fn with_unused_functions(mut self) -> Self {
+        self.unused_functions = true;
+        self
+    }
+
+    //top level declarations are left unresolved so they live in globals.
+    //warnings are only worth reading once the program compiles, so they're
+    //held back until then
+    pub fn resolve(&mut self, stmts: &[Stmt]) {
         self.resolve_stmts(stmts);
-        self.end_scope();
+        if self.status.had_compile_error {
+            return
+        }
+        if self.unused_functions {
+            let unused = self.top_level_functions.iter()
+                .filter(|token| !token.lexeme.starts_with('_'))
+                .filter(|token| !self.global_reads.contains(token.lexeme.as_str()))
+                .map(|token| {
+                    let message = format!("Unused function '{}'.", token.lexeme);
+                    Warning::new("unused-function", token.span.clone(), &message)
+                });
+            let unused: Vec<Warning> = unused.collect();
+            self.warnings.extend(unused);
+        }
+        let mut warnings = mem::take(&mut self.warnings);
+        warnings.sort_by_key(|warning| (warning.span.line, warning.span.column));
+        for warning in warnings {
+            self.status.report_warning(warning);
+        }
     }
 
-    fn resolve_stmts(&mut self, stmts: &Vec<Stmt>) {
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
         for stmt in stmts {
             self.resolve_stmt(stmt);
         }
@@ -59,27 +156,59 @@ impl<'a> Resolver<'a>  {
         expr.accept(self)
     }
     
-    fn resolve_local(&mut self, id: u64, token: Rc<Token>) {
-        let scopes = self.scopes.iter().rev().enumerate();
+    fn resolve_local(&mut self, id: u64, token: Rc<Token>) -> Option<&mut Local> {
+        let scopes = self.scopes.iter_mut().rev().enumerate();
         for (depth, scope) in scopes {
-            if scope.contains_key(&token.lexeme.to_string()) {
+            if let Some(local) = scope.get_mut(token.lexeme.as_str()) {
                 self.interpreter.resolve(id, depth);
-                return
+                return Some(local)
+            }
+        }
+        None
+    }
+
+    fn read_local(&mut self, id: u64, token: Rc<Token>) {
+        let functions = self.functions;
+        let name = token.lexeme.to_string();
+        if let Some(local) = self.resolve_local(id, token) {
+            local.read = true;
+            local.unread_assignment = None;
+            local.captured |= local.function != functions;
+        } else {
+            self.global_reads.insert(name);
+        }
+    }
+
+    //an assignment made in a loop may be read on the next time around, and
+    //one made in a nested function may be read whenever it's called, so
+    //those are never reported as unread
+    fn assign_local(&mut self, id: u64, token: Rc<Token>) {
+        let (functions, loops) = (self.functions, self.loops.len());
+        let span = token.span.clone();
+        if let Some(local) = self.resolve_local(id, token) {
+            match local.function == functions && local.loops == loops {
+                true => local.unread_assignment = Some(span),
+                false => local.captured = true,
             }
         }
     }
 
     fn resolve_func(&mut self, func: &Func, func_type: FuncType) {
         let enclosing_func = mem::replace(&mut self.func_type, func_type);
+        //a function body can't break out of the loop it was declared in
+        let enclosing_loops = mem::take(&mut self.loops);
+        self.functions += 1;
 
         self.begin_scope();
         for param in &func.params {
-            self.declare(Rc::clone(param));
+            self.declare(Rc::clone(param), LocalKind::Parameter);
             self.define(Rc::clone(param));
         }
         self.resolve_stmts(&func.body);
         self.end_scope();
 
+        self.functions -= 1;
+        self.loops = enclosing_loops;
         self.func_type = enclosing_func;
     }
 
@@ -88,26 +217,74 @@ impl<'a> Resolver<'a>  {
     }
 
     fn end_scope(&mut self) {
-        self.scopes.pop();
+        if let Some(scope) = self.scopes.pop() {
+            let warnings = scope.iter().filter_map(|(name, local)| local.warning(name));
+            self.warnings.extend(warnings);
+        }
     }
 
-    fn declare(&mut self, token: Rc<Token>) {
-        let scope = match self.scopes.last_mut() {
-            Some(scope) => scope,
+    //binds `this` or `super` in a scope of its own
+    fn internal(&mut self, name: &str, span: Span) {
+        let mut local = Local::new(span, LocalKind::Internal, self.functions, self.loops.len());
+        local.defined = true;
+        self.scopes.last_mut().unwrap().insert(name.to_string(), local);
+    }
+
+    fn declare(&mut self, token: Rc<Token>, kind: LocalKind) {
+        let (scope, outer) = match self.scopes.split_last_mut() {
+            Some(scopes) => scopes,
             None => return
         };
-        if scope.contains_key(&token.lexeme.to_string()) {
-            let error = RuntimeError::new(Rc::clone(&token),
+        if let Some(previous) = scope.get(token.lexeme.as_str()) {
+            let error = ResolveError::new(Rc::clone(&token),
                 "Already variable with this name declared in this scope."
-            );
-            self.status.report_runtime_error(error)
+            ).with_note(Note::new("previously declared here", Some(previous.span.clone())));
+            self.status.report_compile_error(error)
+        } else if !token.lexeme.starts_with('_') {
+            let shadowed = outer.iter().rev()
+                .find_map(|scope| scope.get(token.lexeme.as_str()));
+            if let Some(shadowed) = shadowed {
+                let message = format!("'{}' shadows an outer binding.", token.lexeme);
+                let note = Note::new("outer binding declared here", Some(shadowed.span.clone()));
+                let warning = Warning::new("shadowed", token.span.clone(), &message)
+                    .with_note(note);
+                self.warnings.push(warning);
+            }
         }
-        scope.insert(token.lexeme.to_string(), false);
+        let local = Local::new(token.span.clone(), kind, self.functions, self.loops.len());
+        scope.insert(token.lexeme.to_string(), local);
     }
 
     fn define(&mut self, token: Rc<Token>) {
-        if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(token.lexeme.to_string(), true);
+        let local = self.scopes.last_mut()
+            .and_then(|scope| scope.get_mut(token.lexeme.as_str()));
+        if let Some(local) = local {
+            local.defined = true;
+        }
+    }
+
+    fn resolve_jump(&mut self, keyword: &Rc<Token>, label: &Option<Rc<Token>>) {
+        let error = match label {
+            _ if self.loops.is_empty() => {
+                let message = format!("Can't use '{}' outside of a loop.", keyword.lexeme);
+                ResolveError::new(Rc::clone(keyword), &message)
+            },
+            Some(label) if !self.loops.iter().flatten().any(|l| *l == label.lexeme) => {
+                let message = format!("No enclosing loop is labeled '{}'.", label.lexeme);
+                ResolveError::new(Rc::clone(label), &message)
+            },
+            _ => return,
+        };
+        self.status.report_compile_error(error);
+    }
+
+    //modules are loaded before the file importing them runs, so imports and
+    //exports can't be nested in blocks or functions
+    fn expect_top_level(&mut self, keyword: &Rc<Token>) {
+        if !self.scopes.is_empty() {
+            let message = format!("Can only {} at the top level.", keyword.lexeme);
+            let error = ResolveError::new(Rc::clone(keyword), &message);
+            self.status.report_compile_error(error);
         }
     }
 
@@ -117,7 +294,7 @@ impl<'a> Resolver<'a>  {
             None => return false,
         };
         match scope.get(&var_name.to_string()) {
-            Some(resolved) => !*resolved,
+            Some(local) => !local.defined,
             None => false,
         }
     }
@@ -133,7 +310,7 @@ impl StmtVisitor for Resolver<'_> {
     }
 
     fn visit_let_stmt(&mut self, stmt: &stmt::Let) -> Self::Output {
-        self.declare(Rc::clone(&stmt.token));
+        self.declare(Rc::clone(&stmt.token), LocalKind::Variable);
         if let Some(init) = &stmt.initializer {
             self.resolve_expr(init);
         }
@@ -141,7 +318,10 @@ impl StmtVisitor for Resolver<'_> {
     }
 
     fn visit_func_stmt(&mut self, stmt: &stmt::Func) -> Self::Output {
-        self.declare(Rc::clone(&stmt.token));
+        if self.scopes.is_empty() {
+            self.top_level_functions.push(Rc::clone(&stmt.token));
+        }
+        self.declare(Rc::clone(&stmt.token), LocalKind::Function);
         self.define(Rc::clone(&stmt.token));
         self.resolve_func(stmt, FuncType::Function);
     }
@@ -164,16 +344,16 @@ impl StmtVisitor for Resolver<'_> {
 
     fn visit_return_stmt(&mut self, stmt: &stmt::Return) -> Self::Output {
         if let FuncType::None = self.func_type {
-            let error = RuntimeError::new(Rc::clone(&stmt.keyword),
+            let error = ResolveError::new(Rc::clone(&stmt.keyword),
                 "Can't return from top-level code.");
-            self.status.report_runtime_error(error);
+            self.status.report_compile_error(error);
         }
 
         if let Some(value) = &stmt.value {
             if let FuncType::Initializer = self.func_type {
-                let error = RuntimeError::new(Rc::clone(&stmt.keyword),
+                let error = ResolveError::new(Rc::clone(&stmt.keyword),
                     "Can't return a value from an initializer.");
-                self.status.report_runtime_error(error);
+                self.status.report_compile_error(error);
             }
             self.resolve_expr(value);
         }
@@ -181,21 +361,74 @@ impl StmtVisitor for Resolver<'_> {
 
     fn visit_while_stmt(&mut self, stmt: &stmt::While) -> Self::Output {
         self.resolve_expr(&stmt.condition);
+        self.loops.push(stmt.label.as_ref().map(|label| Rc::clone(&label.lexeme)));
         self.resolve_stmt(&stmt.body);
+        //the increment runs on every pass, so it belongs to the loop too
+        if let Some(increment) = &stmt.increment {
+            self.resolve_expr(increment);
+        }
+        self.loops.pop();
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &stmt::Break) -> Self::Output {
+        self.resolve_jump(&stmt.keyword, &stmt.label);
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &stmt::Continue) -> Self::Output {
+        self.resolve_jump(&stmt.keyword, &stmt.label);
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &stmt::Throw) -> Self::Output {
+        self.resolve_expr(&stmt.value);
+    }
+
+    fn visit_try_stmt(&mut self, stmt: &stmt::Try) -> Self::Output {
+        self.begin_scope();
+        self.resolve_stmts(&stmt.body);
+        self.end_scope();
+        if let Some(catch) = &stmt.catch {
+            self.begin_scope();
+            self.declare(Rc::clone(&catch.name), LocalKind::Variable);
+            self.define(Rc::clone(&catch.name));
+            self.resolve_stmts(&catch.body);
+            self.end_scope();
+        }
+        if let Some(finally) = &stmt.finally {
+            self.begin_scope();
+            self.resolve_stmts(finally);
+            self.end_scope();
+        }
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &stmt::Import) -> Self::Output {
+        self.expect_top_level(&stmt.keyword);
+        for name in stmt.alias.iter().chain(&stmt.names) {
+            self.declare(Rc::clone(name), LocalKind::Variable);
+            self.define(Rc::clone(name));
+        }
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &stmt::Export) -> Self::Output {
+        self.expect_top_level(&stmt.keyword);
+        self.resolve_stmt(&stmt.declaration);
+        //an exported function is there for importers to call
+        if let Stmt::Func(func) = &*stmt.declaration {
+            self.top_level_functions.retain(|token| !Rc::ptr_eq(token, &func.token));
+        }
     }
 
     fn visit_class_stmt(&mut self, stmt: &stmt::Class) -> Self::Output {
         let enclosing_class = mem::replace(&mut self.class_type, ClassType::Class);
-        self.declare(Rc::clone(&stmt.token));
+        self.declare(Rc::clone(&stmt.token), LocalKind::Class);
         self.define(Rc::clone(&stmt.token));
 
         if let Some(expr) = &stmt.superclass {
             self.class_type = ClassType::SubClass;
             if let Expr::Var(superclass) = expr {
                 if stmt.token.lexeme == superclass.token.lexeme {
-                    let error = RuntimeError::new(Rc::clone(&superclass.token), 
+                    let error = ResolveError::new(Rc::clone(&superclass.token), 
                         "A class can't inherit from itself.");
-                    self.status.report_runtime_error(error);
+                    self.status.report_compile_error(error);
                 }
             self.resolve_expr(expr);
             }
@@ -203,18 +436,18 @@ impl StmtVisitor for Resolver<'_> {
 
         if stmt.superclass.is_some() {
             self.begin_scope();
-            self.scopes.last_mut().unwrap().insert("super".to_string(), true);
+            self.internal("super", stmt.token.span.clone());
         }
         
         self.begin_scope();
-        self.scopes.last_mut().unwrap().insert("this".to_string(), true);
+        self.internal("this", stmt.token.span.clone());
 
         for wrapped_method in &stmt.methods {
             let method = match wrapped_method {
                 Stmt::Func(func) => func,
                 _ => {
-                    let error = RuntimeError::new(Rc::clone(&stmt.token), "Undefined method");
-                    self.status.report_runtime_error(error);
+                    let error = ResolveError::new(Rc::clone(&stmt.token), "Undefined method");
+                    self.status.report_compile_error(error);
                     continue
                 }
             };
@@ -237,17 +470,25 @@ impl ExprVisitor for Resolver<'_> {
 
     fn visit_var_expr(&mut self, expr: &expr::Var) -> Self::Output {
         if self.is_accessed_in_initializer(Rc::clone(&expr.token.lexeme)) {
-            let error = ParseError::new(Rc::clone(&expr.token),
+            let error = ResolveError::new(Rc::clone(&expr.token),
                 "Can't read local variable in its own initializer."
             );
             self.status.report_compile_error(error);
         }
-        self.resolve_local(expr.id, Rc::clone(&expr.token));
+        self.read_local(expr.id, Rc::clone(&expr.token));
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &expr::Lambda) -> Self::Output {
+        self.resolve_func(&expr.func, FuncType::Function);
     }
 
     fn visit_assign_expr(&mut self, expr: &expr::Assign) -> Self::Output {
         self.resolve_expr(&expr.value);
-        self.resolve_local(expr.id, Rc::clone(&expr.token));
+        //`x += 1` reads x before it assigns it
+        if expr.update.is_some() {
+            self.read_local(expr.id, Rc::clone(&expr.token));
+        }
+        self.assign_local(expr.id, Rc::clone(&expr.token));
     }
 
     fn visit_binary_expr(&mut self, expr: &expr::Binary) -> Self::Output {
@@ -286,33 +527,63 @@ impl ExprVisitor for Resolver<'_> {
         self.resolve_expr(&expr.object);
     }
 
+    fn visit_list_expr(&mut self, expr: &expr::List) -> Self::Output {
+        for element in &expr.elements {
+            self.resolve_expr(element);
+        }
+    }
+
+    fn visit_interpolation_expr(&mut self, expr: &expr::Interpolation) -> Self::Output {
+        for part in &expr.parts {
+            self.resolve_expr(part);
+        }
+    }
+
+    fn visit_map_expr(&mut self, expr: &expr::Map) -> Self::Output {
+        for (key, value) in &expr.entries {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_index_expr(&mut self, expr: &expr::Index) -> Self::Output {
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &expr::SetIndex) -> Self::Output {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
     fn visit_this_expr(&mut self, expr: &expr::This) -> Self::Output {
         match self.class_type {
             ClassType::None => {
-                let error = RuntimeError::new(Rc::clone(&expr.keyword), 
+                let error = ResolveError::new(Rc::clone(&expr.keyword), 
                         "Can't use 'this' outside of a class.");
-                self.status.report_runtime_error(error);
+                self.status.report_compile_error(error);
             },
-            _ => self.resolve_local(expr.id, Rc::clone(&expr.keyword)),
+            _ => self.read_local(expr.id, Rc::clone(&expr.keyword)),
         }
     }
 
     fn visit_super_expr(&mut self, expr: &expr::Super) -> Self::Output {
         match self.class_type {
             ClassType::None => {
-                let error = RuntimeError::new(Rc::clone(&expr.keyword), 
+                let error = ResolveError::new(Rc::clone(&expr.keyword), 
                     "Can't use 'super' outside of a class.");
-                self.status.report_runtime_error(error);
+                self.status.report_compile_error(error);
             }
 
             ClassType::Class => {
-                let error = RuntimeError::new(Rc::clone(&expr.keyword), 
+                let error = ResolveError::new(Rc::clone(&expr.keyword), 
                     "Can't use 'super' in a class with no superclass.");
-                self.status.report_runtime_error(error);
+                self.status.report_compile_error(error);
             }
 
             ClassType::SubClass => 
-                self.resolve_local(expr.id, Rc::clone(&expr.keyword)),
+                self.read_local(expr.id, Rc::clone(&expr.keyword)),
         }
     }
 }
@@ -3,45 +3,112 @@ use std::{
     rc::Rc,
 };
 
+use unicode_xid::UnicodeXID;
+
 use crate::{
-    token::{Token, TokenType, Value},
+    token::{Token, TokenType, Value, Span},
     error::{ErrorStatus, ScanError},
 };
 
+//every reserved word and the token it scans to, also what the REPL completes
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("if", TokenType::If),
+    ("else", TokenType::Else),
+    ("and", TokenType::And),
+    ("or", TokenType::Or),
+    ("true", TokenType::True),
+    ("false", TokenType::False),
+    ("for", TokenType::For),
+    ("while", TokenType::While),
+    ("let", TokenType::Let),
+    ("fn", TokenType::Fn),
+    ("class", TokenType::Class),
+    ("return", TokenType::Return),
+    ("Nil", TokenType::Nil),
+    ("print", TokenType::Print),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("break", TokenType::Break),
+    ("continue", TokenType::Continue),
+    ("import", TokenType::Import),
+    ("export", TokenType::Export),
+    ("from", TokenType::From),
+    ("as", TokenType::As),
+    ("throw", TokenType::Throw),
+    ("try", TokenType::Try),
+    ("catch", TokenType::Catch),
+    ("finally", TokenType::Finally),
+];
+
+//positions are indexes into `chars`, spans convert them back to byte offsets
 pub struct Scanner<'a> {
     source: String,
+    chars: Vec<(usize, char)>,
+    file: Rc<String>,
     tokens: Vec<Rc<Token>>,
+    trivia: Vec<Rc<Token>>, //comments, which only the formatter needs
     start: usize,
     current: usize,
     line: usize,
+    line_start: usize, //index of the first character on the current line
+    start_line: usize,
+    start_column: usize,
+    interpolations: Vec<usize>, //open braces inside each `${` being scanned
+    unterminated: bool, //the source ended inside a string
     status: &'a mut ErrorStatus,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(status: &'a mut ErrorStatus, source: String,) -> Scanner<'a> {
+    pub fn new(status: &'a mut ErrorStatus, source: String, file: Rc<String>
+        ) -> Scanner<'a> {
+        let chars = source.char_indices().collect();
         Scanner {
             source,
+            chars,
+            file,
             tokens: vec!(),
+            trivia: vec!(),
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            interpolations: vec!(),
+            unterminated: false,
             status,
         }
     }
 
+    //true once scanning stops inside a string or an interpolated expression,
+    //which more input could still finish
+    pub fn is_unterminated(&self) -> bool {
+        self.unterminated || !self.interpolations.is_empty()
+    }
+
+    //the comments scanned so far, in source order
+    pub fn trivia(&mut self) -> Vec<Rc<Token>> {
+        std::mem::take(&mut self.trivia)
+    }
+
     pub fn scan_tokens(&mut self) -> Vec<Rc<Token>> {
         while !(self.is_at_end()) {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column(self.start);
             if let Err(error) = self.scan_token() {
                 self.status.report_compile_error(error);
             }
         }
 
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column(self.start);
         let end_token = Rc::new(Token::new(
             TokenType::Eof,
             Rc::new(String::new()),
             Arc::new(RwLock::new(Value::None)),
-            self.line
+            self.span(),
         ));
 
         self.tokens.push(end_token);
@@ -52,94 +119,187 @@ impl<'a> Scanner<'a> {
     fn scan_token(&mut self) -> Result<(), ScanError>{
         self.advance();
         match self.curr() {
-            "(" => self.add_token(TokenType::OpenParen),
-            ")" => self.add_token(TokenType::CloseParen),
-            "{" => self.add_token(TokenType::OpenBrace),
-            "}" => self.add_token(TokenType::CloseBrace),
-            "," => self.add_token(TokenType::Comma),
-            "." => self.add_token(TokenType::Dot),
-            "-" => self.add_token(TokenType::Minus),
-            "+" => self.add_token(TokenType::Plus),
-            ";" => self.add_token(TokenType::Semicolon),
-            "*" => self.add_token(TokenType::Star),
-            "!" => self.add_token(match self.peek() {
-                "=" => TokenType::BangEqual,
-                _ => TokenType::Bang,
-            }),
-            "=" => match self.peek() {
-                "=" => {self.advance(); self.add_token(TokenType::EqualEqual);},
+            '(' => self.add_token(TokenType::OpenParen),
+            ')' => self.add_token(TokenType::CloseParen),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.add_token(TokenType::OpenBrace)
+            },
+            '}' => match self.interpolations.last_mut() {
+                //closes a `${`, so the string it interrupted carries on
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.tokenize_string()?
+                },
+                Some(depth) => {*depth -= 1; self.add_token(TokenType::CloseBrace)},
+                None => self.add_token(TokenType::CloseBrace),
+            },
+            '[' => self.add_token(TokenType::OpenBracket),
+            ']' => self.add_token(TokenType::CloseBracket),
+            ',' => self.add_token(TokenType::Comma),
+            '.' => self.add_token(TokenType::Dot),
+            '-' => match self.peek() {
+                '-' => {self.advance(); self.add_token(TokenType::MinusMinus)},
+                '>' => {self.advance(); self.add_token(TokenType::Arrow)},
+                _ => self.add_operator(TokenType::Minus, TokenType::MinusEqual),
+            },
+            '+' => match self.peek() {
+                '+' => {self.advance(); self.add_token(TokenType::PlusPlus)},
+                _ => self.add_operator(TokenType::Plus, TokenType::PlusEqual),
+            },
+            ';' => self.add_token(TokenType::Semicolon),
+            ':' => self.add_token(TokenType::Colon),
+            '*' => match self.peek() {
+                '*' => {
+                    self.advance();
+                    self.add_operator(TokenType::StarStar, TokenType::StarStarEqual)
+                },
+                _ => self.add_operator(TokenType::Star, TokenType::StarEqual),
+            },
+            '%' => self.add_operator(TokenType::Percent, TokenType::PercentEqual),
+            '^' => self.add_operator(TokenType::Caret, TokenType::CaretEqual),
+            '&' => self.add_operator(TokenType::Ampersand, TokenType::AmpersandEqual),
+            '|' => self.add_operator(TokenType::Pipe, TokenType::PipeEqual),
+            '~' => match self.peek() {
+                '/' => {
+                    self.advance();
+                    self.add_operator(TokenType::TildeSlash, TokenType::TildeSlashEqual)
+                },
+                _ => return Err(
+                    ScanError::new(self.span(), "Unexpected character.")
+                ),
+            },
+            '!' => match self.peek() {
+                '=' => {self.advance(); self.add_token(TokenType::BangEqual)},
+                _ => self.add_token(TokenType::Bang),
+            },
+            '=' => match self.peek() {
+                '=' => {self.advance(); self.add_token(TokenType::EqualEqual);},
                 _ => self.add_token(TokenType::Equal),
             },           
-            ">" => match self.peek() {
-                "=" => {self.advance(); self.add_token(TokenType::GreaterEqual)},
+            '>' => match self.peek() {
+                '=' => {self.advance(); self.add_token(TokenType::GreaterEqual)},
+                '>' => {
+                    self.advance();
+                    self.add_operator(TokenType::GreaterGreater, TokenType::GreaterGreaterEqual)
+                },
                 _ => self.add_token(TokenType::Greater),
             },
-            "<" => match self.peek() {
-                "=" => {self.advance(); self.add_token(TokenType::LessEqual)},
+            '<' => match self.peek() {
+                '=' => {self.advance(); self.add_token(TokenType::LessEqual)},
+                '<' => {
+                    self.advance();
+                    self.add_operator(TokenType::LessLess, TokenType::LessLessEqual)
+                },
                 _ => self.add_token(TokenType::Less),
             },
-            "/" => match self.peek() {
-                "/" => {self.advance(); self.skip_comment()},
-                _ => self.add_token(TokenType::Slash),
+            '/' => match self.peek() {
+                '/' => {self.advance(); self.comment()},
+                _ => self.add_operator(TokenType::Slash, TokenType::SlashEqual),
             },
 
-            (" " | "\r" | "\t") => {} //Whitespace chars are skipped
-            "\n" => self.line += 1,
-            "\"" => self.tokenize_string()?,
+            c if c.is_whitespace() && c != '\n' => {} //Whitespace chars are skipped
+            '\n' => self.new_line(),
+            '"' => self.tokenize_string()?,
 
             c if self.is_digit(c) => self.tokenize_number()?,
             c if self.is_alpha(c) => self.tokenize_identifier(),
             
             _ => return Err(
-                ScanError::new(self.line, "Unexpected character.")
+                ScanError::new(self.span(), "Unexpected character.")
             ),
         }
         Ok(())
     }
     
+    //scans from an opening quote, or from the `}` that ends an interpolated
+    //expression, up to the closing quote or the next `${`
     fn tokenize_string(&mut self) -> Result<(), ScanError> {
-        while (self.peek() != "\"") && (!self.is_at_end()) {
-            if self.peek() == "\n" {self.line += 1};
+        let mut value = String::new();
+        let mut error = None;
+        let token_type = loop {
+            if self.is_at_end() {
+                self.unterminated = true;
+                return Err(
+                    ScanError::new(self.span(), "Unterminated string.")
+                )
+            }
             self.advance();
-        }
-        if self.is_at_end() {
-            return Err(
-                ScanError::new(self.line, "Unterminated string.")
-            )
-        }
-        self.advance();
-
-        let value = 
-            self.source[self.start+1..self.current-1].to_string();
+            match self.curr() {
+                '"' => break TokenType::String,
+                '$' if self.peek() == '{' => {
+                    self.advance();
+                    self.interpolations.push(0);
+                    break TokenType::Interpolation
+                },
+                '\\' => match self.escape() {
+                    Ok(c) => value.push(c),
+                    Err(invalid) => {error.get_or_insert(invalid);},
+                },
+                '\n' => {value.push('\n'); self.new_line()},
+                c => value.push(c),
+            }
+        };
 
         self.add_literal_token(
-            TokenType::String,
+            token_type,
             Arc::new(RwLock::new(Value::String(value)))
         );
-        Ok(())
+        //the token is still added so an escape error doesn't also cause parse errors
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    //called after the backslash has been consumed
+    fn escape(&mut self) -> Result<char, ScanError> {
+        let start = self.current - 1;
+        let column = self.column(start);
+        if self.is_at_end() {
+            self.unterminated = true;
+            return Err(ScanError::new(self.span(), "Unterminated string."))
+        }
+        self.advance();
+        let c = match self.curr() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            '$' => '$',
+            other => {
+                if other == '\n' {self.new_line()}
+                let message = format!("Invalid escape sequence '\\{}'.", other);
+                let span = Span::new(Rc::clone(&self.file), self.offset(start),
+                                     self.offset(self.current), self.line, column);
+                return Err(ScanError::new(span, &message))
+            },
+        };
+        Ok(c)
     }
 
     fn tokenize_number(&mut self) -> Result<(), ScanError> {
         while self.is_digit(self.peek()) {
             self.advance()
         };
-        if (self.peek() == ".") && (self.is_digit(self.peek_next())) {
+        if (self.peek() == '.') && (self.is_digit(self.peek_next())) {
             self.advance()
         };
         while self.is_digit(self.peek()) {
             self.advance();
         };
-        let num = 
-            self.source[self.start..self.current]
-            .to_string()
-            .parse::<f64>();
+        let num = self.text().parse::<f64>();
         match num {
             Ok(n) => self.add_literal_token(
                 TokenType::Number,
                 Arc::new(RwLock::new(Value::Num(n)))
             ),
             Err(_) => return Err(
-                ScanError::new(self.line, "Failed to parse number.")
+                ScanError::new(self.span(), "Failed to parse number.")
             ),
         }
         Ok(())
@@ -149,8 +309,8 @@ impl<'a> Scanner<'a> {
         while self.is_alpha_numeric(self.peek()) {
             self.advance();
         };
-        let text = &self.source[self.start..self.current];
-        let token_type = match self.match_keyword(text) {
+        let text = self.text();
+        let token_type = match self.match_keyword(&text) {
             Some(t) => t,
             None => TokenType::Identifier,
         };
@@ -158,25 +318,17 @@ impl<'a> Scanner<'a> {
     }
 
     fn match_keyword(&self, keyword: &str) -> Option<TokenType> {
-        Some(match keyword {
-            "if" => TokenType::If,
-            "else" => TokenType::Else,
-            "and" => TokenType::And,
-            "or" => TokenType::Or,
-            "true" => TokenType::True,
-            "false" => TokenType::False,
-            "for" => TokenType::For,
-            "while" => TokenType::While,
-            "let" => TokenType::Let,
-            "fn" => TokenType::Fn,
-            "class" => TokenType::Class,
-            "return" => TokenType::Return,
-            "Nil" =>TokenType::Nil,
-            "print" => TokenType::Print,
-            "super" => TokenType::Super,
-            "this" => TokenType::This,
-            _ => return None,   
-        })
+        KEYWORDS.iter()
+            .find(|(word, _)| *word == keyword)
+            .map(|(_, token_type)| token_type.clone())
+    }
+
+    //adds the compound assignment form when the operator is followed by `=`
+    fn add_operator(&mut self, operator: TokenType, assign: TokenType) {
+        match self.peek() {
+            '=' => {self.advance(); self.add_token(assign)},
+            _ => self.add_token(operator),
+        }
     }
 
     fn add_token(&mut self, token_type: TokenType) {
@@ -190,56 +342,95 @@ impl<'a> Scanner<'a> {
 
     fn push_token(
         &mut self, token_type: TokenType, literal: Arc<RwLock<Value>>) {
-        let text = self.source[self.start..self.current].to_string();
+        let text = self.text();
         let token = Rc::new(Token::new(
             token_type,
             Rc::new(text),
             literal,
-            self.line,
+            self.span(),
         ));
         self.tokens.push(token);
     }
 
+    fn span(&self) -> Span {
+        Span::new(Rc::clone(&self.file), self.offset(self.start),
+                  self.offset(self.current), self.start_line, self.start_column)
+    }
+
+    //the source text of the token being scanned
+    fn text(&self) -> String {
+        self.source[self.offset(self.start)..self.offset(self.current)].to_string()
+    }
+
+    //byte offset of the character at `index`, or the end of the source
+    fn offset(&self, index: usize) -> usize {
+        match self.chars.get(index) {
+            Some((offset, _)) => *offset,
+            None => self.source.len(),
+        }
+    }
+
+    fn column(&self, index: usize) -> usize {
+        index - self.line_start + 1
+    }
+
+    //called once the newline itself has been consumed
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.len() 
+        self.current >= self.chars.len()
     }
 
     fn advance(&mut self) {
         self.current += 1;
     }
 
-    fn curr(&self) -> &str {
-        &self.source[self.current-1..=self.current-1]
+    fn curr(&self) -> char {
+        self.chars[self.current - 1].1
     }
 
-    fn peek(&self) -> &str {
-        if self.is_at_end() {return "\0"}
-        &self.source[self.current..=self.current]
+    fn peek(&self) -> char {
+        self.char_at(self.current)
     }
 
-    fn peek_next(&self) -> &str {
-        if self.current+1 >= self.source.len() {return "\0"};
-        &self.source[self.current+1..=self.current+1]
+    fn peek_next(&self) -> char {
+        self.char_at(self.current + 1)
+    }
+
+    fn char_at(&self, index: usize) -> char {
+        match self.chars.get(index) {
+            Some((_, c)) => *c,
+            None => '\0',
+        }
     }
     
-    fn skip_comment(&mut self) {
-        while (self.peek() != "\n") && (!self.is_at_end()) {
+    //runs to the end of the line. the newline is left to be scanned as usual
+    fn comment(&mut self) {
+        while (self.peek() != '\n') && (!self.is_at_end()) {
             self.advance();
         }
+        let comment = Rc::new(Token::new(
+            TokenType::Comment,
+            Rc::new(self.text()),
+            Arc::new(RwLock::new(Value::None)),
+            self.span(),
+        ));
+        self.trivia.push(comment);
     }
 
-    fn is_digit(&self, c: &str) -> bool {
-        ("0"..="9").contains(&c)
+    fn is_digit(&self, c: char) -> bool {
+        c.is_ascii_digit()
     }
 
-
-    fn is_alpha(&self, c: &str) -> bool {
-           ("a"..="z").contains(&c)
-        || ("A"..="Z").contains(&c)
-        || c == "_"
+    //identifiers follow Unicode's XID rules, with `_` allowed to start one
+    fn is_alpha(&self, c: char) -> bool {
+        c.is_xid_start() || c == '_'
     }
 
-    fn is_alpha_numeric(&self, c: &str) -> bool {
-        self.is_alpha(c) || self.is_digit(c)
+    fn is_alpha_numeric(&self, c: char) -> bool {
+        c.is_xid_continue()
     }
 }
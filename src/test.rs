@@ -1,10 +1,804 @@
 #[cfg(test)]
 mod tests {
-    use crate::test_file::test_file;
+    use crate::{Lax, LaxError, Value, Arity, RuntimeError, SharedBuffer, Backend, ErrorFormat, repl::{Repl, complete}};
+    use crate::{AstFormat, ast::printer::AstPrinter, optimizer::Optimizer};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_eval_returns_last_expression() {
+        let mut session = Lax::new();
+        session.eval("fn add(a, b) { return a + b; }").unwrap();
+        let value = session.eval("add(2, 3);").unwrap();
+        assert_eq!(value, Value::Num(5.0));
+    }
+
+    #[test]
+    fn test_eval_reports_errors() {
+        let mut session = Lax::with_output(
+            Box::new(SharedBuffer::new()), Box::new(SharedBuffer::new()));
+        assert!(matches!(session.eval("let = 1;"), Err(LaxError::Compile)));
+        assert!(matches!(session.eval("-\"a\";"), Err(LaxError::Runtime(_))));
+        assert!(matches!(session.run_file("tests/missing.lax"), Err(LaxError::Io(_))));
+    }
 
     #[test]
     fn test_compiler() {
+        let stdout = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(stdout.clone()), Box::new(SharedBuffer::new()));
         let path = "tests/fib.lax";
-        test_file(path)
+        session.run_file(path).unwrap();
+        assert_eq!(stdout.contents().lines().last(), Some("4181"));
+    }
+
+    #[test]
+    fn test_top_level_bindings_are_globals() {
+        let stdout = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(stdout.clone()), Box::new(SharedBuffer::new()));
+        //top level declarations live in globals, so they can be redeclared
+        //and a function can call one declared after it
+        assert_eq!(session.eval("let a = 1; let a = 2; a;").unwrap(), Value::Num(2.0));
+        let source = concat!(
+            "fn even(n) { if (n == 0) return true; return odd(n - 1); }\n",
+            "fn odd(n) { if (n == 0) return false; return even(n - 1); }\n",
+            "even(4);",
+        );
+        assert_eq!(session.eval(source).unwrap(), Value::Bool(true));
+
+        //the resolver's errors stop the script before it runs
+        let error = session.eval("print \"ran\"; return 1;").unwrap_err();
+        assert!(matches!(error, LaxError::Compile));
+        assert_eq!(error.exit_code(), 65);
+        assert_eq!(stdout.contents(), "");
+    }
+
+    #[test]
+    fn test_variadic_native() {
+        let stderr = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(SharedBuffer::new()), Box::new(stderr.clone()));
+        session.define_native("sum", Arity::AtLeast(1), |_, args| {
+            let mut total = 0.0;
+            for arg in args {
+                match &*arg.read().unwrap() {
+                    Value::Num(num) => total += num,
+                    _ => return Err(RuntimeError::without_token("Can only sum numbers.")),
+                }
+            }
+            Ok(Value::Num(total))
+        });
+        assert_eq!(session.eval("sum(1, 2, 3);").unwrap(), Value::Num(6.0));
+        match session.eval("sum();") {
+            Err(LaxError::Runtime(error)) => {
+                assert_eq!(error.to_string(), "[line 1] Expected at least 1 argument but got 0.")
+            },
+            _ => panic!("expected a runtime error"),
+        }
+
+        match session.eval("sum(1, \"a\");") {
+            Err(LaxError::Runtime(error)) => {
+                assert_eq!(error.to_string(), "[line 1] Can only sum numbers.")
+            },
+            _ => panic!("expected a runtime error"),
+        }
+        assert!(stderr.contents().contains("error: Can only sum numbers.\n"));
+
+        //reset forgets globals but keeps every native, even shadowed ones
+        session.eval("let leftover = 1; fn len(x) { return -1; }").unwrap();
+        session.reset();
+        assert!(matches!(session.eval("leftover;"), Err(LaxError::Runtime(_))));
+        assert_eq!(session.eval("sum(1, 2);").unwrap(), Value::Num(3.0));
+        assert_eq!(session.eval("len([1, 2]);").unwrap(), Value::Num(2.0));
+    }
+
+    #[test]
+    fn test_captured_output() {
+        let stdout = SharedBuffer::new();
+        let stderr = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(stdout.clone()), Box::new(stderr.clone()));
+
+        session.run_file("tests/func.lax").unwrap();
+        assert_eq!(stdout.contents(), "Hi, Dear Reader!\n");
+
+        assert!(session.eval("print undefined;").is_err());
+        assert_eq!(stderr.contents(), concat!(
+            "error: 'undefined' cannot be found in current scope.\n",
+            " --> <eval>:1:7\n",
+            "  |\n",
+            "1 | print undefined;\n",
+            "  |       ^^^^^^^^^\n",
+        ));
+    }
+
+    #[test]
+    fn test_multibyte_columns() {
+        let stderr = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(SharedBuffer::new()), Box::new(stderr.clone()));
+
+        assert!(session.eval("let é = \"ü\"; print -é;").is_err());
+        assert_eq!(stderr.contents(), concat!(
+            "error: Operand must be a number.\n",
+            " --> <eval>:1:20\n",
+            "  |\n",
+            "1 | let é = \"ü\"; print -é;\n",
+            "  |                    ^\n",
+        ));
+    }
+
+    #[test]
+    fn test_json_diagnostics() {
+        let stderr = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(SharedBuffer::new()), Box::new(stderr.clone()));
+        session.set_error_format(ErrorFormat::Json);
+
+        assert!(session.eval("fn f() { let a = 1; let a = 2; }").is_err());
+        assert_eq!(stderr.contents(), concat!(
+            r#"{"severity":"error","phase":"resolve","code":"resolve-error","#,
+            r#""message":"Already variable with this name declared in this scope.","#,
+            r#""file":"<eval>","span":{"start":24,"end":25,"line":1,"column":25},"#,
+            r#""notes":[{"message":"previously declared here","file":"<eval>","#,
+            r#""span":{"start":13,"end":14,"line":1,"column":14}}]}"#, "\n",
+        ));
+
+        stderr.clear();
+        session.eval("fn g() { let b = 1; }").unwrap();
+        assert!(session.run_file("tests/missing.lax").is_err());
+        assert_eq!(stderr.contents(), concat!(
+            r#"{"severity":"warning","phase":"resolve","code":"unused-variable","#,
+            r#""message":"Unused variable 'b'.","#,
+            r#""file":"<eval#2>","span":{"start":13,"end":14,"line":1,"column":14},"notes":[]}"#, "\n",
+            r#"{"severity":"error","phase":"io","code":"io-error","#,
+            r#""message":"Could not read 'tests/missing.lax': No such file or directory (os error 2)","#,
+            r#""file":"tests/missing.lax","span":null,"notes":[]}"#, "\n",
+        ));
+
+        //every error has a code, and a thrown value is told apart from an
+        //error the runtime raised
+        stderr.clear();
+        assert!(session.eval("-\"a\";").is_err());
+        let runtime = stderr.contents();
+        assert!(runtime.starts_with(
+            r#"{"severity":"error","phase":"runtime","code":"runtime-error","#), "{}", runtime);
+        stderr.clear();
+        assert!(session.eval("throw 1;").is_err());
+        let thrown = stderr.contents();
+        assert!(thrown.starts_with(
+            r#"{"severity":"error","phase":"runtime","code":"uncaught-throw","#), "{}", thrown);
+    }
+
+    #[test]
+    fn test_modules_are_cached_per_session() {
+        let stdout = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(stdout.clone()), Box::new(SharedBuffer::new()));
+
+        session.eval("import \"tests/modules/math.lax\" as math;").unwrap();
+        session.set_backend(Backend::Vm);
+        session.eval("from \"tests/modules/math.lax\" import double;").unwrap();
+        assert_eq!(session.eval("double(math.pi);").unwrap(), Value::Num(6.0));
+        assert_eq!(stdout.contents(), "loading math\n");
+    }
+
+    #[test]
+    fn test_runtime_error_stack_trace() {
+        let source = "fn inner(n) {\n  return -n;\n}\nfn outer(n) { return inner(n); }\nouter(\"a\");";
+        for backend in [Backend::Tree, Backend::Vm] {
+            let stderr = SharedBuffer::new();
+            let mut session = Lax::with_output(
+                Box::new(SharedBuffer::new()), Box::new(stderr.clone()));
+            session.set_backend(backend);
+
+            assert!(session.eval(source).is_err());
+            assert_eq!(stderr.contents(), concat!(
+                "error: Operand must be a number.\n",
+                " --> <eval>:2:10\n",
+                "  |\n",
+                "2 |   return -n;\n",
+                "  |          ^\n",
+                "  = stack trace:\n",
+                "      at inner (<eval>:2)\n",
+                "      at outer (<eval>:4)\n",
+                "      at <script> (<eval>:5)\n",
+            ));
+        }
+    }
+
+    #[test]
+    fn test_stack_overflow_trace_is_collapsed() {
+        let stderr = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(SharedBuffer::new()), Box::new(stderr.clone()));
+        session.set_backend(Backend::Vm);
+
+        assert!(session.eval("fn f(n) { return f(n + 1); }\nf(0);").is_err());
+        assert_eq!(stderr.contents(), concat!(
+            "error: Stack overflow.\n",
+            " --> <eval>:1:25\n",
+            "  |\n",
+            "1 | fn f(n) { return f(n + 1); }\n",
+            "  |                         ^\n",
+            "  = stack trace:\n",
+            "      at f (<eval>:1)\n",
+            "      ... 254 more frames in f\n",
+            "      at <script> (<eval>:2)\n",
+        ));
+    }
+
+    #[test]
+    fn test_each_run_quotes_its_own_source() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let stderr = SharedBuffer::new();
+            let mut session = Lax::with_output(
+                Box::new(SharedBuffer::new()), Box::new(stderr.clone()));
+            session.set_backend(backend);
+
+            session.eval("fn f(n) {\n  return -n;\n}").unwrap();
+            assert!(session.eval("f(\"a\"); print \"padding\";\nprint 1;").is_err());
+            assert_eq!(stderr.contents(), concat!(
+                "error: Operand must be a number.\n",
+                " --> <eval>:2:10\n",
+                "  |\n",
+                "2 |   return -n;\n",
+                "  |          ^\n",
+                "  = stack trace:\n",
+                "      at f (<eval>:2)\n",
+                "      at <script> (<eval#2>:1)\n",
+            ));
+        }
+    }
+
+    #[test]
+    fn test_resolver_warnings() {
+        let source = concat!(
+            "fn f(a, _b) {\n",
+            "  let x = 1;\n",
+            "  let y = 2;\n",
+            "  print y;\n",
+            "  y = 3;\n",
+            "  {\n",
+            "    let a = 4;\n",
+            "    print a;\n",
+            "  }\n",
+            "  for (let i = 0; i < 2; i++) {}\n",
+            "  let _unused = 5;\n",
+            "  return a;\n",
+            "}\n",
+            "f(1, 2);",
+        );
+        let (stdout, stderr) = (SharedBuffer::new(), SharedBuffer::new());
+        let mut session = Lax::with_output(
+            Box::new(stdout.clone()), Box::new(stderr.clone()));
+
+        //warnings don't stop the program
+        session.eval(source).unwrap();
+        assert_eq!(stdout.contents(), "2\n4\n");
+        assert_eq!(session.status.warning_count, 3);
+        assert_eq!(stderr.contents(), concat!(
+            "warning: Unused variable 'x'.\n",
+            " --> <eval>:2:7\n",
+            "  |\n",
+            "2 |   let x = 1;\n",
+            "  |       ^\n",
+            "warning: Value assigned to 'y' is never read.\n",
+            " --> <eval>:5:3\n",
+            "  |\n",
+            "5 |   y = 3;\n",
+            "  |   ^\n",
+            "warning: 'a' shadows an outer binding.\n",
+            " --> <eval>:7:9\n",
+            "  |\n",
+            "7 |     let a = 4;\n",
+            "  |         ^\n",
+            "  = note: outer binding declared here (<eval>:1:6)\n",
+        ));
+    }
+
+    #[test]
+    fn test_unused_function_warnings() {
+        let (stdout, stderr) = (SharedBuffer::new(), SharedBuffer::new());
+        let mut session = Lax::with_output(
+            Box::new(stdout.clone()), Box::new(stderr.clone()));
+        session.run_file("tests/warnings/unused_function.lax").unwrap();
+        assert_eq!(session.status.warning_count, 1);
+        assert!(stderr.contents().starts_with(concat!(
+            "warning: Unused function 'never_called'.\n",
+            " --> tests/warnings/unused_function.lax:2:4\n",
+        )));
+
+        //later eval or REPL input may still call it
+        stderr.clear();
+        session.eval("fn later() {}").unwrap();
+        assert_eq!(stderr.contents(), "");
+    }
+
+    #[test]
+    fn test_optimizer() {
+        let source = concat!(
+            "print 1 + 2 * (3 - 1);\n",
+            "print \"a\" + \"b${1 + 1}\";\n",
+            "if (!!(1 < 2) or x) print y; else print z;\n",
+            "while (!!x and !!y) print !!x;\n",
+            "while (false) print x;\n",
+            "fn f(a) { return a; print a; }\n",
+            "print -\"a\" + 1;\n",
+            "print \"${true}\";\n",
+        );
+        let mut session = Lax::new();
+        let stmts = session.syntax(source, "<eval>").unwrap();
+        let optimized = Optimizer::optimize(&stmts);
+        assert_eq!(AstPrinter::tree(&optimized), concat!(
+            "Print\n",
+            "  Literal 5\n",
+            "Print\n",
+            "  Literal \"ab2\"\n",
+            "Print\n",
+            "  Var y\n",
+            "While\n",
+            "  Logical and\n",
+            "    Var x\n",
+            "    Var y\n",
+            "  Print\n",
+            "    Unary !\n",
+            "      Unary !\n",
+            "        Var x\n",
+            "Block\n",
+            "Fn f (a)\n",
+            "  Return\n",
+            "    Var a\n",
+            //fails at runtime, so it's left for the error to be raised there
+            "Print\n",
+            "  Binary +\n",
+            "    Unary -\n",
+            "      Literal \"a\"\n",
+            "    Literal 1\n",
+            "Print\n",
+            "  Literal \"true\"\n",
+        ));
+    }
+
+    #[test]
+    fn test_formatter() {
+        let source = concat!(
+            "// keeps comments\n",
+            "fn add(a,b){return a+b;}\n",
+            "\n\n\n",
+            "let m={\"k\":[1,-2]};   // trailing\n",
+            "for(let i=0;i<2;i++){if(i==0){print \"${i}!\";}else print -i;}\n",
+            "let f=|x|x*2;let g=fn(){return 1;}();\n",
+            "class A<B{init(){super.init();}}\n",
+        );
+        let mut session = Lax::with_output(
+            Box::new(SharedBuffer::new()), Box::new(SharedBuffer::new()));
+        let formatted = session.format(source, "<fmt>").unwrap();
+        assert_eq!(formatted, concat!(
+            "// keeps comments\n",
+            "fn add(a, b) {\n",
+            "    return a + b;\n",
+            "}\n",
+            "\n",
+            "let m = {\"k\": [1, -2]}; // trailing\n",
+            "for (let i = 0; i < 2; i++) {\n",
+            "    if (i == 0) {\n",
+            "        print \"${i}!\";\n",
+            "    } else print -i;\n",
+            "}\n",
+            "let f = |x| x * 2;\n",
+            "let g = fn () {\n",
+            "    return 1;\n",
+            "}();\n",
+            "class A < B {\n",
+            "    init() {\n",
+            "        super.init();\n",
+            "    }\n",
+            "}\n",
+        ));
+        assert_eq!(session.format(&formatted, "<fmt>").unwrap(), formatted);
+        assert!(matches!(session.format("print 1 +;", "<fmt>"), Err(LaxError::Compile)));
+
+        //signs in a row keep their spaces, or they'd rescan as `--` and `++`
+        let source = "let x = 1;\nprint - -x;\nprint -(-x);\nprint 1 - -x;\nprint - --x;\n";
+        let formatted = session.format(source, "<fmt>").unwrap();
+        assert_eq!(formatted, source);
+        let tree = |session: &mut Lax, source: &str| {
+            AstPrinter::tree(&session.syntax(source, "<fmt>").unwrap())
+        };
+        assert_eq!(tree(&mut session, &formatted), tree(&mut session, source));
+        let mangled = "let x = 1;\nprint - - x;\nprint - ( - x);\nprint 1 - - x;\nprint -   --x;\n";
+        assert_eq!(session.format(mangled, "<fmt>").unwrap(), source);
+    }
+
+    #[test]
+    fn test_dump_tokens_and_ast() {
+        //ids come from a counter shared by every parse, so they're masked
+        fn mask_ids(dump: &str) -> String {
+            let mut masked = String::new();
+            let mut in_id = false;
+            for c in dump.chars() {
+                match c {
+                    '#' => {in_id = true; masked.push_str("#N")},
+                    c if in_id && c.is_ascii_digit() => {},
+                    c => {in_id = false; masked.push(c)},
+                }
+            }
+            masked
+        }
+
+        let source = "let g = 1;\nfn f(a) { let b = a + g; return |x| \"${b}\"; }\n";
+        let (stdout, stderr) = (SharedBuffer::new(), SharedBuffer::new());
+        let mut session = Lax::with_output(
+            Box::new(stdout.clone()), Box::new(stderr.clone()));
+
+        session.dump_tokens("let g = 1;", "<dump>").unwrap();
+        assert_eq!(stdout.contents(), concat!(
+            "1:1     Let             let\n",
+            "1:5     Identifier      g\n",
+            "1:7     Equal           =\n",
+            "1:9     Number          1  1\n",
+            "1:10    Semicolon       ;\n",
+            "1:11    Eof\n",
+        ));
+
+        stdout.clear();
+        session.dump_ast(source, "<dump>", AstFormat::Tree).unwrap();
+        assert_eq!(mask_ids(&stdout.contents()), concat!(
+            "Let g\n",
+            "  Literal 1\n",
+            "Fn f (a)\n",
+            "  Let b\n",
+            "    Binary +\n",
+            "      Var a [#N depth 0]\n",
+            "      Var g [#N global]\n",
+            "  Return\n",
+            "    Lambda (x)\n",
+            "      Return\n",
+            "        Interpolation\n",
+            "          Var b [#N depth 1]\n",
+        ));
+
+        stdout.clear();
+        session.dump_ast("print -x;", "<dump>", AstFormat::Sexpr).unwrap();
+        let sexpr = stdout.contents();
+        assert!(sexpr.starts_with("(Print (Unary - (Var x :id "));
+        assert!(sexpr.ends_with(" :depth global)))\n"));
+
+        stdout.clear();
+        session.dump_ast("print x;", "<dump>", AstFormat::Json).unwrap();
+        let json = stdout.contents();
+        assert!(json.starts_with("[{\"label\":\"Print\",\"children\":[{\"label\":\"Var x\",\"id\":"));
+        assert!(json.ends_with(",\"depth\":null,\"children\":[]}]}]\n"));
+
+        //what parsed is still shown, without depths
+        stdout.clear();
+        stderr.clear();
+        let result = session.dump_ast("print 1;\nprint +;", "<dump>", AstFormat::Sexpr);
+        assert!(matches!(result, Err(LaxError::Compile)));
+        assert_eq!(stdout.contents(), "(Print (Literal 1))\n");
+        assert!(stderr.contents().starts_with("error: Expected expression."));
+    }
+
+    #[test]
+    fn test_repl_buffers_and_echoes() {
+        let stdout = SharedBuffer::new();
+        let stderr = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(stdout.clone()), Box::new(stderr.clone()));
+        let mut repl = Repl::new();
+
+        for line in ["fn add(a,\n", "  b) {\n", "  return a + b;\n"] {
+            assert!(repl.feed(&mut session, line));
+            assert_eq!(repl.prompt(), "... ");
+        }
+        assert!(repl.feed(&mut session, "}\n"));
+        assert_eq!(repl.prompt(), "> ");
+        repl.feed(&mut session, "let s = \"a\n");
+        repl.feed(&mut session, "b\";\n");
+        repl.feed(&mut session, "add(1, 2);\n");
+        repl.feed(&mut session, "print Nil;\n");
+        repl.feed(&mut session, ":env\n");
+        repl.feed(&mut session, ":ast -x * (1 + 2)\n");
+        assert_eq!(stdout.contents(), concat!(
+            "3\n",
+            "nil\n",
+            "add = <fn add>\n",
+            "s = a\nb\n",
+            "Expr\n",
+            "  Binary *\n",
+            "    Unary -\n",
+            "      Var x\n",
+            "    Grouping\n",
+            "      Binary +\n",
+            "        Literal 1\n",
+            "        Literal 2\n",
+        ));
+
+        stdout.clear();
+        repl.feed(&mut session, ":reset\n");
+        repl.feed(&mut session, ":env\n");
+        repl.feed(&mut session, ":nope\n");
+        assert_eq!(stdout.contents(), "");
+        assert!(stderr.contents().starts_with("Unknown command ':nope'."));
+        assert!(!repl.feed(&mut session, ":quit\n"));
+    }
+
+    #[test]
+    fn test_repl_completion() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut session = Lax::new();
+            session.set_backend(backend);
+            session.eval("
+                class Shape { area() { return 0; } }
+                class Square < Shape { init(side) { this.side = side; } }
+                let square = Square(2);
+                let holder = Shape();
+                holder.inner = square;
+                let primes = [2, 3];").unwrap();
+            let globals = Arc::clone(&session.interpreter().globals);
+
+            assert_eq!(complete(&globals, "print squ"), (6, vec!("square".to_string())));
+            assert_eq!(complete(&globals, "pr"), (0, vec!("primes".to_string(), "print".to_string())));
+            assert_eq!(complete(&globals, "fin"), (0, vec!("finally".to_string())));
+            let members = complete(&globals, "square.").1;
+            assert_eq!(members, vec!("area", "init", "side"));
+            assert_eq!(complete(&globals, "1 + holder.inner.s"), (17, vec!("side".to_string())));
+            assert_eq!(complete(&globals, "primes.").1, Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn test_vm_closures_and_super() {
+        let mut session = Lax::new();
+        session.set_backend(Backend::Vm);
+        let source = "
+            fn counter() { let n = 0; fn inc() { n = n + 1; return n; } return inc; }
+            let c = counter(); c(); c();
+            class A { init(x) { this.x = x; } get() { return this.x; } }
+            class B < A { init(x) { super.init(x + c()); } }
+            B(10).get();";
+        assert_eq!(session.eval(source).unwrap(), Value::Num(13.0));
+    }
+
+    #[test]
+    fn test_vm_constant_pool() {
+        //more globals than a one byte index can name, with every name repeated
+        let mut source: String = (0..300).map(|i| format!("let v{} = {};\n", i, i)).collect();
+        source.push_str("v299 = v299 + v0 + v1;\nv299 + v298;");
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut session = Lax::new();
+            session.set_backend(backend);
+            assert_eq!(session.eval(&source).unwrap(), Value::Num(598.0), "{:?}", backend);
+        }
+
+        //a chunk that runs out of two byte indexes is reported once
+        let source: String = (0..70000).map(|i| format!("{};\n", i)).collect();
+        let stderr = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(SharedBuffer::new()), Box::new(stderr.clone()));
+        session.set_backend(Backend::Vm);
+        assert!(matches!(session.eval(&source), Err(LaxError::Compile)));
+        assert_eq!(stderr.contents().matches("Too many constants in one chunk.").count(), 1);
+    }
+
+    #[test]
+    fn test_expression_spans() {
+        use std::rc::Rc;
+        use crate::{
+            ast::{parser::Parser, stmt::Stmt},
+            scanner::Scanner,
+            ErrorStatus,
+        };
+
+        let mut status = ErrorStatus::new();
+        let source = "let a = 1;\nprint a + (2 * 3);".to_string();
+        let tokens = Scanner::new(&mut status, source, Rc::new("spans.lax".to_string()))
+            .scan_tokens();
+        let stmts = Parser::new(&mut status, tokens).parse();
+
+        let print = match &stmts[1] {
+            Stmt::Print(print) => print,
+            _ => panic!("expected a print statement"),
+        };
+        let span = print.expr.span();
+        assert_eq!((span.start, span.end, span.line, span.column), (17, 28, 2, 7));
+        assert_eq!(stmts[1].span().to_string(), "spans.lax:2:1");
+    }
+}
+
+//runs every script under tests/ and checks it against the annotations in
+//its comments:
+//  // expect: <line>                 a line printed to stdout
+//  // Prints "<line>".               the same, in prose
+//  // expect error: <message>        a compile error reported on this line
+//  // expect runtime error: <message> a runtime error raised on this line
+//  // backend: tree|vm               only run the script on one backend
+#[cfg(test)]
+mod golden {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{Lax, SharedBuffer, Backend};
+
+    #[derive(Default)]
+    struct Expectations {
+        output: Vec<String>,
+        checks_output: bool, //a script with no annotations only has to run cleanly
+        errors: Vec<String>,
+        exit_code: i32,
+        backends: Vec<Backend>,
+    }
+
+    impl Expectations {
+        fn parse(source: &str) -> Self {
+            let mut expected = Expectations::default();
+            for (i, line) in source.lines().enumerate() {
+                let comment = match comment(line) {
+                    Some(comment) => comment,
+                    None => continue,
+                };
+                let line_num = i + 1;
+                if let Some(text) = comment.strip_prefix("expect: ") {
+                    expected.output.push(text.to_string());
+                } else if let Some(text) = comment.strip_prefix("Prints \"") {
+                    let text = text.trim_end_matches('.').trim_end_matches('"');
+                    expected.output.push(text.to_string());
+                } else if let Some(text) = comment.strip_prefix("expect error: ") {
+                    expected.errors.push(format!("[line {}] {}", line_num, text));
+                    expected.exit_code = 65;
+                } else if let Some(text) = comment.strip_prefix("expect runtime error: ") {
+                    expected.errors.push(format!("[line {}] {}", line_num, text));
+                    expected.exit_code = 70;
+                } else if let Some(name) = comment.strip_prefix("backend: ") {
+                    expected.backends.push(name.parse().unwrap());
+                }
+            }
+            if expected.output.is_empty() && expected.errors.is_empty() {
+                expected.output = legacy_output(source);
+            }
+            expected.checks_output = !expected.output.is_empty() || !expected.errors.is_empty();
+            if expected.backends.is_empty() {
+                expected.backends = vec!(Backend::Tree, Backend::Vm);
+            }
+            expected
+        }
+    }
+
+    //some scripts that predate `expect:` note what a line prints in any
+    //comment after it, as `// "x".` or `//x`
+    fn legacy_output(source: &str) -> Vec<String> {
+        source.lines()
+            .filter(|line| !line.trim_start().starts_with("//"))
+            .filter_map(comment)
+            .map(|comment| {
+                let text = comment.strip_suffix('.').unwrap_or(comment);
+                text.trim_matches('"').to_string()
+            })
+            .collect()
+    }
+
+    //the text after the first `//` that is not inside a string literal
+    fn comment(line: &str) -> Option<&str> {
+        let mut in_string = false;
+        let mut prev = ' ';
+        for (i, c) in line.char_indices() {
+            match c {
+                '"' => in_string = !in_string,
+                '/' if prev == '/' && !in_string => return Some(line[i + 1..].trim()),
+                _ => {},
+            }
+            prev = c;
+        }
+        None
+    }
+
+    fn lax_files(dir: &Path, files: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                lax_files(&path, files);
+            } else if path.extension().is_some_and(|ext| ext == "lax") {
+                files.push(path);
+            }
+        }
+    }
+
+    //reduces each rendered diagnostic to `[line N] message`
+    fn diagnostics(rendered: &str) -> Vec<String> {
+        let mut diagnostics = vec!();
+        let mut lines = rendered.lines().peekable();
+        while let Some(line) = lines.next() {
+            let message = match line.strip_prefix("error: ") {
+                Some(message) => message,
+                None => continue,
+            };
+            let location = lines.peek()
+                .and_then(|next| next.trim_start().strip_prefix("--> "))
+                .and_then(|location| location.rsplit(':').nth(1));
+            match location {
+                Some(line_num) => diagnostics.push(format!("[line {}] {}", line_num, message)),
+                None => diagnostics.push(message.to_string()),
+            }
+        }
+        diagnostics
+    }
+
+    fn check(path: &Path, backend: Backend, expected: &Expectations) -> Result<(), String> {
+        let stdout = SharedBuffer::new();
+        let stderr = SharedBuffer::new();
+        let mut session = Lax::with_output(
+            Box::new(stdout.clone()), Box::new(stderr.clone()));
+        session.set_backend(backend);
+
+        let exit_code = match session.run_file(path) {
+            Ok(()) => 0,
+            Err(error) => error.exit_code(),
+        };
+        let output: Vec<String> = stdout.contents().lines().map(String::from).collect();
+        let errors = diagnostics(&stderr.contents());
+
+        let describe = |what: &str| format!("{} ({:?}): {}", path.display(), backend, what);
+        if expected.checks_output && output != expected.output {
+            return Err(describe(&format!(
+                "expected output {:?} but got {:?}", expected.output, output)))
+        }
+        if errors != expected.errors {
+            return Err(describe(&format!(
+                "expected errors {:?} but got {:?}", expected.errors, errors)))
+        }
+        if exit_code != expected.exit_code {
+            return Err(describe(&format!(
+                "expected exit code {} but got {}", expected.exit_code, exit_code)))
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_golden_files() {
+        let mut files = vec!();
+        lax_files(Path::new("tests"), &mut files);
+        files.sort();
+        assert!(!files.is_empty(), "no .lax files found under tests/");
+
+        let mut failures = vec!();
+        for path in &files {
+            let source = fs::read_to_string(path).unwrap();
+            let expected = Expectations::parse(&source);
+            for backend in &expected.backends {
+                if let Err(failure) = check(path, *backend, &expected) {
+                    failures.push(failure);
+                }
+            }
+        }
+        assert!(failures.is_empty(), "{} failures across {} scripts:\n{}",
+                failures.len(), files.len(), failures.join("\n"));
+    }
+
+    //formatting keeps every expectation and changes nothing the second time
+    #[test]
+    fn test_golden_files_format_stably() {
+        let mut files = vec!();
+        lax_files(Path::new("tests"), &mut files);
+        files.sort();
+
+        for path in &files {
+            let source = fs::read_to_string(path).unwrap();
+            let mut session = Lax::with_output(
+                Box::new(SharedBuffer::new()), Box::new(SharedBuffer::new()));
+            let file = path.display().to_string();
+            //scripts that are meant not to compile can't be formatted
+            let formatted = match session.format(&source, &file) {
+                Ok(formatted) => formatted,
+                Err(_) => continue,
+            };
+            let expected = Expectations::parse(&source);
+            let reformatted = Expectations::parse(&formatted);
+            assert_eq!(reformatted.output, expected.output, "{}", file);
+            assert_eq!(reformatted.errors.len(), expected.errors.len(), "{}", file);
+            assert_eq!(session.format(&formatted, &file).unwrap(), formatted, "{}", file);
+        }
     }
 }
@@ -1,12 +1,19 @@
 use std::{
+    collections::BTreeMap,
+    cmp::Ordering,
     sync::{RwLock, Arc, Mutex},
     rc::Rc,
     fmt,
 };
 
-use crate::callables::{
-    lax_object::LaxObject,
-    Callable,
+use crate::{
+    callables::{
+        lax_object::LaxObject,
+        Callable,
+    },
+    vm::object::VmObject,
+    module::Module,
+    error::Exception,
 };
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,52 +22,197 @@ pub enum TokenType{
     OpenParen, CloseParen,
     OpenBrace, CloseBrace,
     Comma, Dot, Minus, Plus,
-    Semicolon, Slash, Star,
+    OpenBracket, CloseBracket,
+    Semicolon, Slash, Star, Colon,
+    Percent, Caret, Ampersand, Pipe,
 
     //Comparison Tokens
     Equal, EqualEqual,
     Bang, BangEqual,
     Greater, GreaterEqual,
     Less, LessEqual,
+
+    //Two char operators
+    StarStar, TildeSlash,
+    LessLess, GreaterGreater,
+    PlusPlus, MinusMinus,
+    Arrow,
+
+    //Compound assignment
+    PlusEqual, MinusEqual, StarEqual, SlashEqual,
+    PercentEqual, StarStarEqual, TildeSlashEqual,
+    AmpersandEqual, PipeEqual, CaretEqual,
+    LessLessEqual, GreaterGreaterEqual,
        
     //Literals
     Identifier, String, Number,
+    Interpolation, //a string segment that ends in `${`
+
+    //Trivia, kept apart from the tokens the parser sees
+    Comment,
 
     //Keywords
     If, Else, And, Or, True, False,
     For, While, Let, Fn, Class, Return,
     Nil, Print, Super, This,
+    Break, Continue,
+    Import, Export, From, As,
+    Throw, Try, Catch, Finally,
 
     Eof,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value {
     String(String),
     Num(f64),
     Bool(bool),
     Callable(Callable),
     LaxObject(Arc<Mutex<LaxObject>>),
+    VmObject(VmObject),
+    List(Arc<RwLock<Vec<Value>>>),
+    Map(Arc<RwLock<BTreeMap<MapKey, Value>>>),
+    Module(Arc<Module>),
+    Exception(Arc<Exception>),
     None
 }
 
+//the subset of values that can key a map. maps are ordered by key so
+//printing and iteration don't depend on hashing
+#[derive(Debug, Clone)]
+pub enum MapKey {
+    Bool(bool),
+    Num(f64),
+    String(String),
+}
+
+impl MapKey {
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Bool(val) => Ok(MapKey::Bool(*val)),
+            //-0 and 0 are equal numbers so they must be the same key
+            Value::Num(val) if *val == 0.0 => Ok(MapKey::Num(0.0)),
+            Value::Num(val) => Ok(MapKey::Num(*val)),
+            Value::String(val) => Ok(MapKey::String(val.clone())),
+            _ => Err("Map keys must be strings, numbers or booleans.".to_string()),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Bool(val) => Value::Bool(*val),
+            MapKey::Num(val) => Value::Num(*val),
+            MapKey::String(val) => Value::String(val.clone()),
+        }
+    }
+}
+
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MapKey::Bool(a), MapKey::Bool(b)) => a.cmp(b),
+            (MapKey::Num(a), MapKey::Num(b)) => a.total_cmp(b),
+            (MapKey::String(a), MapKey::String(b)) => a.cmp(b),
+            (MapKey::Bool(_), _) => Ordering::Less,
+            (_, MapKey::Bool(_)) => Ordering::Greater,
+            (MapKey::Num(_), _) => Ordering::Less,
+            (_, MapKey::Num(_)) => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MapKey {}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
+
+//a range of source text: byte offsets plus the 1-based line and column
+//of its first character
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Span {
+    pub file: Rc<String>,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(file: Rc<String>, start: usize, end: usize,
+        line: usize, column: usize) -> Self {
+        Span { file, start, end, line, column }
+    }
+
+    //the smallest span covering both self and other
+    pub fn to(&self, other: &Span) -> Span {
+        let (first, last) = match self.start <= other.start {
+            true => (self, other),
+            false => (other, self),
+        };
+        Span {
+            end: first.end.max(last.end),
+            ..first.clone()
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: Rc<String>,
     pub literal: Arc<RwLock<Value>>,
-    pub line: usize,
+    pub span: Span,
 }
 
 impl Token {
     pub fn new(token_type: TokenType, lexeme: Rc<String>,
-        literal: Arc<RwLock<Value>>, line: usize) -> Token {
+        literal: Arc<RwLock<Value>>, span: Span) -> Token {
         Token {
             token_type,
             lexeme,
             literal,
-            line,
+            span,
         }
     }
+
+    pub fn line(&self) -> usize {
+        self.span.line
+    }
+
+    //one line of --dump-tokens: where the token starts, its type, its source
+    //text and, for literals, the value it carries
+    pub fn describe(&self) -> String {
+        let position = format!("{}:{}", self.span.line, self.span.column);
+        let token_type = format!("{:?}", self.token_type);
+        let lexeme = self.lexeme.replace('\n', "\\n");
+        let line = format!("{:<8}{:<16}{}", position, token_type, lexeme);
+        let line = match &*self.literal.read().unwrap() {
+            Value::None => line,
+            Value::String(value) => format!("{}  {:?}", line, value),
+            value => format!("{}  {}", line, value),
+        };
+        line.trim_end().to_string()
+    }
 }
 
 impl fmt::Display for Token {
@@ -76,12 +228,218 @@ impl fmt::Debug for Token {
 }
 
 
+//operator semantics shared by the tree walking interpreter and the vm
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(val) => *val,
+            Value::None => false,
+            _ => true,
+        }
+    }
+
+    pub fn unary(&self, operator: &TokenType) -> Result<Value, &'static str> {
+        match (self, operator) {
+            (Value::Num(val), TokenType::Minus) => Ok(Value::Num(-val)),
+            (_, TokenType::Minus) => Err("Operand must be a number."),
+            (_, TokenType::Bang) => Ok(Value::Bool(!self.is_truthy())),
+            _ => Ok(Value::None),
+        }
+    }
+
+    pub fn binary(&self, operator: &TokenType, right: &Value
+        ) -> Result<Value, &'static str> {
+        let value = match (self, right) {
+            (Value::Num(left), Value::Num(right)) => match operator {
+                TokenType::Plus => Value::Num(left + right),
+                TokenType::Minus => Value::Num(left - right),
+                TokenType::Star => Value::Num(left * right),
+                TokenType::Slash => Value::Num(left / right),
+                TokenType::StarStar => Value::Num(left.powf(*right)),
+                //integer division and remainder both truncate toward zero, so
+                //a == (a ~/ b) * b + a % b
+                TokenType::TildeSlash | TokenType::Percent if *right == 0.0 =>
+                    return Err("Division by zero."),
+                TokenType::TildeSlash => Value::Num((left / right).trunc()),
+                TokenType::Percent => Value::Num(left % right),
+                TokenType::Ampersand | TokenType::Pipe | TokenType::Caret
+                | TokenType::LessLess | TokenType::GreaterGreater => {
+                    Value::Num(bitwise(*left, operator, *right)? as f64)
+                },
+                TokenType::Greater => Value::Bool(left > right),
+                TokenType::GreaterEqual => Value::Bool(left >= right),
+                TokenType::Less => Value::Bool(left < right),
+                TokenType::LessEqual => Value::Bool(left <= right),
+                TokenType::EqualEqual => Value::Bool(left == right),
+                TokenType::BangEqual => Value::Bool(left != right),
+                _ => return Err("Operator cannot be used on numbers"),
+            },
+
+            (Value::String(left), Value::String(right)) => match operator {
+                TokenType::Plus => Value::String(left.to_string() + right),
+                TokenType::EqualEqual => Value::Bool(left == right),
+                TokenType::BangEqual => Value::Bool(left != right),
+                _ => return Err("Operator cannot be used on strings"),
+            },
+
+            (left, right) => match operator {
+                TokenType::EqualEqual => Value::Bool(left == right),
+                TokenType::BangEqual => Value::Bool(left != right),
+                _ => return Err("Operator cannot be used on values of this type"),
+            }
+        };
+        Ok(value)
+    }
+}
+
+impl Value {
+    pub fn new_list(elements: Vec<Value>) -> Self {
+        Value::List(Arc::new(RwLock::new(elements)))
+    }
+
+    pub fn new_map(entries: BTreeMap<MapKey, Value>) -> Self {
+        Value::Map(Arc::new(RwLock::new(entries)))
+    }
+
+    //the names `.` can reach on this value, for the REPL to complete
+    pub fn members(&self) -> Vec<String> {
+        match self {
+            Value::LaxObject(object) => object.lock().unwrap().members(),
+            Value::VmObject(VmObject::Instance(instance)) => {
+                let instance = instance.read().unwrap();
+                let mut names: Vec<String> = instance.fields.keys().cloned().collect();
+                names.extend(instance.class.read().unwrap().methods.keys().cloned());
+                names
+            },
+            Value::Module(module) => module.exports.iter().cloned().collect(),
+            Value::Exception(_) => vec!("message".to_string(), "line".to_string()),
+            _ => vec!(),
+        }
+    }
+
+    //reads a field or export without binding methods or raising errors
+    pub fn field(&self, name: &str) -> Option<Value> {
+        match self {
+            Value::LaxObject(object) => object.lock().unwrap().field(name),
+            Value::VmObject(VmObject::Instance(instance)) => {
+                instance.read().unwrap().fields.get(name).cloned()
+            },
+            Value::Module(module) => module.lookup(name).map(|value| value.read().unwrap().clone()),
+            Value::Exception(exception) => exception.get(name),
+            _ => None,
+        }
+    }
+
+    pub fn index(&self, index: &Value) -> Result<Value, String> {
+        match self {
+            Value::List(list) => {
+                let list = list.read().unwrap();
+                let position = list_position(index, list.len())?;
+                Ok(list[position].clone())
+            },
+            Value::Map(map) => {
+                let key = MapKey::from_value(index)?;
+                match map.read().unwrap().get(&key) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(format!("Undefined key '{}'.", key)),
+                }
+            },
+            _ => Err("Only lists and maps can be indexed.".to_string()),
+        }
+    }
+
+    pub fn set_index(&self, index: &Value, value: Value) -> Result<(), String> {
+        match self {
+            Value::List(list) => {
+                let mut list = list.write().unwrap();
+                let position = list_position(index, list.len())?;
+                list[position] = value;
+                Ok(())
+            },
+            Value::Map(map) => {
+                let key = MapKey::from_value(index)?;
+                map.write().unwrap().insert(key, value);
+                Ok(())
+            },
+            _ => Err("Only lists and maps can be indexed.".to_string()),
+        }
+    }
+}
+
+//checks that `index` is a whole number that falls inside a list of `len`
+pub fn list_position(index: &Value, len: usize) -> Result<usize, String> {
+    let index = match index {
+        Value::Num(num) if num.fract() == 0.0 => *num,
+        _ => return Err("List index must be a whole number.".to_string()),
+    };
+    match index >= 0.0 && index < len as f64 {
+        true => Ok(index as usize),
+        false => Err(format!("Index {} is out of range for a list of length {}.", index, len)),
+    }
+}
+
+//bitwise operators work on the 64 bit integer a number holds exactly
+fn bitwise(left: f64, operator: &TokenType, right: f64) -> Result<i64, &'static str> {
+    let left = integer(left)?;
+    let right = integer(right)?;
+    let value = match operator {
+        TokenType::Ampersand => left & right,
+        TokenType::Pipe => left | right,
+        TokenType::Caret => left ^ right,
+        TokenType::LessLess | TokenType::GreaterGreater => {
+            let shift = match u32::try_from(right) {
+                Ok(shift) if shift < 64 => shift,
+                _ => return Err("Shift amount must be between 0 and 63."),
+            };
+            match operator {
+                TokenType::LessLess => left << shift,
+                _ => left >> shift,
+            }
+        },
+        _ => return Err("Operator cannot be used on numbers"),
+    };
+    Ok(value)
+}
+
+fn integer(num: f64) -> Result<i64, &'static str> {
+    //2^63 itself is out of range, so the upper bound is exclusive
+    match num.fract() == 0.0 && num >= i64::MIN as f64 && num < i64::MAX as f64 {
+        true => Ok(num as i64),
+        false => Err("Operands must be integers."),
+    }
+}
+
+impl TokenType {
+    //the binary operator a compound assignment applies, so `+=` gives `+`
+    pub fn compound_operator(&self) -> Option<TokenType> {
+        Some(match self {
+            TokenType::PlusEqual | TokenType::PlusPlus => TokenType::Plus,
+            TokenType::MinusEqual | TokenType::MinusMinus => TokenType::Minus,
+            TokenType::StarEqual => TokenType::Star,
+            TokenType::SlashEqual => TokenType::Slash,
+            TokenType::PercentEqual => TokenType::Percent,
+            TokenType::StarStarEqual => TokenType::StarStar,
+            TokenType::TildeSlashEqual => TokenType::TildeSlash,
+            TokenType::AmpersandEqual => TokenType::Ampersand,
+            TokenType::PipeEqual => TokenType::Pipe,
+            TokenType::CaretEqual => TokenType::Caret,
+            TokenType::LessLessEqual => TokenType::LessLess,
+            TokenType::GreaterGreaterEqual => TokenType::GreaterGreater,
+            _ => return None,
+        })
+    }
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::String(a), Self::String(b)) => a == b,
             (Self::Num(a), Self::Num(b)) => a == b,
             (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::List(a), Self::List(b)) => Arc::ptr_eq(a, b),
+            (Self::Map(a), Self::Map(b)) => Arc::ptr_eq(a, b),
+            (Self::Module(a), Self::Module(b)) => Arc::ptr_eq(a, b),
+            (Self::Exception(a), Self::Exception(b)) => Arc::ptr_eq(a, b),
             _ => false
         }
     }
@@ -89,12 +447,56 @@ impl PartialEq for Value {
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, &mut vec!())
+    }
+}
+
+impl Value {
+    //`visiting` holds the lists and maps being written, so one that contains
+    //itself is written as `[...]` or `{...}` the second time instead of forever
+    fn write(&self, f: &mut fmt::Formatter, visiting: &mut Vec<usize>) -> fmt::Result {
         match self {
             Value::String(value) => write!(f, "{}", value),
             Value::Num(value) => write!(f, "{}", value),
             Value::Bool(value) => write!(f, "{}", value),
             Value::Callable(value) => write!(f, "{}", value),
             Value::LaxObject(value) => write!(f, "{}", value.lock().unwrap()),
+            Value::VmObject(value) => write!(f, "{}", value),
+            Value::List(list) => {
+                let id = Arc::as_ptr(list) as usize;
+                if visiting.contains(&id) {
+                    return write!(f, "[...]")
+                }
+                visiting.push(id);
+                write!(f, "[")?;
+                for (i, element) in list.read().unwrap().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.write(f, visiting)?;
+                }
+                visiting.pop();
+                write!(f, "]")
+            },
+            Value::Map(map) => {
+                let id = Arc::as_ptr(map) as usize;
+                if visiting.contains(&id) {
+                    return write!(f, "{{...}}")
+                }
+                visiting.push(id);
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.read().unwrap().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", key)?;
+                    value.write(f, visiting)?;
+                }
+                visiting.pop();
+                write!(f, "}}")
+            },
+            Value::Module(module) => write!(f, "{}", module),
+            Value::Exception(exception) => write!(f, "{}", exception),
             Value::None => write!(f, "nil"),
         }
     }
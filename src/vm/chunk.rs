@@ -0,0 +1,238 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+};
+
+use crate::token::{Value, Span};
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Closure,
+    CloseUpvalue,
+    Return,
+    Class,
+    Inherit,
+    Method,
+    BuildList,
+    GetIndex,
+    SetIndex,
+    BuildMap,
+    Stringify,
+    Power,
+    IntDivide,
+    Modulo,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Dup,
+    Bury,
+    Import,
+    PushHandler,
+    PopHandler,
+    Throw,
+    Wide, //the next instruction's constant index is two bytes
+}
+
+impl OpCode {
+    //every opcode is declared in order so the byte is the discriminant
+    const ALL: [OpCode; 58] = [
+        OpCode::Constant, OpCode::Nil, OpCode::True, OpCode::False,
+        OpCode::Pop, OpCode::GetLocal, OpCode::SetLocal, OpCode::GetGlobal,
+        OpCode::DefineGlobal, OpCode::SetGlobal, OpCode::GetUpvalue,
+        OpCode::SetUpvalue, OpCode::GetProperty, OpCode::SetProperty,
+        OpCode::GetSuper, OpCode::Equal, OpCode::NotEqual, OpCode::Greater,
+        OpCode::GreaterEqual, OpCode::Less, OpCode::LessEqual, OpCode::Add,
+        OpCode::Subtract, OpCode::Multiply, OpCode::Divide, OpCode::Not,
+        OpCode::Negate, OpCode::Print, OpCode::Jump, OpCode::JumpIfFalse,
+        OpCode::Loop, OpCode::Call, OpCode::Closure, OpCode::CloseUpvalue,
+        OpCode::Return, OpCode::Class, OpCode::Inherit, OpCode::Method,
+        OpCode::BuildList, OpCode::GetIndex, OpCode::SetIndex, OpCode::BuildMap,
+        OpCode::Stringify, OpCode::Power, OpCode::IntDivide, OpCode::Modulo,
+        OpCode::BitAnd, OpCode::BitOr, OpCode::BitXor, OpCode::ShiftLeft,
+        OpCode::ShiftRight, OpCode::Dup, OpCode::Bury,
+        OpCode::Import, OpCode::PushHandler, OpCode::PopHandler, OpCode::Throw,
+        OpCode::Wide,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+}
+
+//source spans are run length encoded as (span, number of bytes)
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    strings: HashMap<String, usize>, //index of each string constant, names included
+    spans: Vec<(Span, usize)>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Chunk::default()
+    }
+
+    pub fn write(&mut self, byte: u8, span: &Span) {
+        self.code.push(byte);
+        match self.spans.last_mut() {
+            Some((last, count)) if last == span => *count += 1,
+            _ => self.spans.push((span.clone(), 1)),
+        }
+    }
+
+    pub fn write_op(&mut self, op: OpCode, span: &Span) {
+        self.write(op as u8, span);
+    }
+
+    //every use of a global or property repeats its name, so equal strings
+    //share one constant
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        if let Value::String(string) = &value {
+            if let Some(index) = self.strings.get(string) {
+                return *index
+            }
+            self.strings.insert(string.clone(), self.constants.len());
+        }
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    pub fn span_at(&self, offset: usize) -> Span {
+        let mut end = 0;
+        for (span, count) in &self.spans {
+            end += count;
+            if offset < end {
+                return span.clone()
+            }
+        }
+        self.spans.last().map_or(Span::default(), |(span, _)| span.clone())
+    }
+
+    pub fn line_at(&self, offset: usize) -> usize {
+        self.span_at(offset).line
+    }
+
+    pub fn disassemble(&self, name: &str) -> String {
+        let mut out = format!("== {} ==\n", name);
+        let mut offset = 0;
+        while offset < self.code.len() {
+            offset = self.disassemble_instruction(&mut out, offset)
+                .expect("writing to a String cannot fail");
+        }
+        out
+    }
+
+    fn disassemble_instruction(&self, out: &mut String, offset: usize
+        ) -> Result<usize, fmt::Error> {
+        write!(out, "{:04} ", offset)?;
+        match offset > 0 && self.line_at(offset) == self.line_at(offset - 1) {
+            true => write!(out, "   | ")?,
+            false => write!(out, "{:4} ", self.line_at(offset))?,
+        }
+
+        let op = match OpCode::from_byte(self.code[offset]) {
+            Some(op) => op,
+            None => {
+                writeln!(out, "Unknown opcode {}", self.code[offset])?;
+                return Ok(offset + 1)
+            },
+        };
+        //a wide instruction is shown as one, with its two byte constant
+        let (op, name, operand, width) = match op {
+            OpCode::Wide => match OpCode::from_byte(self.code[offset + 1]) {
+                Some(op) => (op, format!("Wide {:?}", op), offset + 2, 2),
+                None => {
+                    writeln!(out, "Wide")?;
+                    return Ok(offset + 1)
+                },
+            },
+            op => (op, format!("{:?}", op), offset + 1, 1),
+        };
+        let constant = match width {
+            2 => u16::from_be_bytes([self.code[operand], self.code[operand + 1]]) as usize,
+            _ => self.code[operand] as usize,
+        };
+        match op {
+            OpCode::Constant | OpCode::GetGlobal | OpCode::DefineGlobal
+            | OpCode::SetGlobal | OpCode::GetProperty | OpCode::SetProperty
+            | OpCode::GetSuper | OpCode::Class | OpCode::Method
+            | OpCode::Import => {
+                writeln!(out, "{:<16} {:4} '{}'", name, constant, self.constants[constant])?;
+                Ok(operand + width)
+            },
+            OpCode::GetLocal | OpCode::SetLocal | OpCode::GetUpvalue
+            | OpCode::SetUpvalue | OpCode::Call | OpCode::BuildList
+            | OpCode::BuildMap | OpCode::Dup | OpCode::Bury => {
+                writeln!(out, "{:<16} {:4}", name, self.code[offset + 1])?;
+                Ok(offset + 2)
+            },
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop | OpCode::PushHandler => {
+                let jump = u16::from_be_bytes(
+                    [self.code[offset + 1], self.code[offset + 2]]) as usize;
+                let target = match op {
+                    OpCode::Loop => offset + 3 - jump,
+                    _ => offset + 3 + jump,
+                };
+                writeln!(out, "{:<16} {:4} -> {}", name, offset, target)?;
+                Ok(offset + 3)
+            },
+            OpCode::Closure => {
+                let value = &self.constants[constant];
+                writeln!(out, "{:<16} {:4} {}", name, constant, value)?;
+                let mut offset = operand + width;
+                if let Value::VmObject(object) = value {
+                    for _ in 0..object.upvalue_count() {
+                        let kind = match self.code[offset] {
+                            1 => "local",
+                            _ => "upvalue",
+                        };
+                        writeln!(out, "{:04}    |                     {} {}",
+                                 offset, kind, self.code[offset + 1])?;
+                        offset += 2;
+                    }
+                }
+                Ok(offset)
+            },
+            _ => {
+                writeln!(out, "{}", name)?;
+                Ok(offset + 1)
+            },
+        }
+    }
+}
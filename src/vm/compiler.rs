@@ -0,0 +1,899 @@
+use std::{
+    mem,
+    rc::Rc,
+    sync::Arc,
+};
+
+use crate::{
+    ast::{
+        expr::{self, Expr, AcceptExprVisitor, ExprVisitor},
+        stmt::{self, Stmt, AcceptStmtVisitor, StmtVisitor},
+    },
+    callables::callable::FuncType,
+    module::module_path,
+    error::{ErrorStatus, CompileError},
+    token::{Token, TokenType, Value, Span},
+};
+
+use super::{
+    chunk::OpCode,
+    object::{Function, VmObject},
+};
+
+const MAX_SLOTS: usize = 256;
+
+struct Local {
+    name: String,
+    depth: Option<usize>, //None until the initializer has been compiled
+    is_captured: bool,
+}
+
+struct UpvalueRef {
+    index: u8,
+    is_local: bool,
+}
+
+//jumps out of a loop body are patched once the loop's end is known
+struct LoopState {
+    label: Option<String>,
+    scope_depth: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+//a try enclosing the code being compiled. returns and loop exits that leave
+//it pop its handler and run its finally block on the way out
+#[derive(Clone)]
+struct TryState {
+    finally: Option<Vec<Stmt>>,
+    handlers: usize, //how many handlers the try has installed at this point
+    loops: usize, //how many loops enclosed the try
+}
+
+struct FunctionState {
+    function: Function,
+    func_type: FuncType,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    loops: Vec<LoopState>,
+    tries: Vec<TryState>,
+    scope_depth: usize,
+    too_many_constants: bool, //already reported for this chunk
+}
+
+impl FunctionState {
+    fn new(name: String, func_type: FuncType) -> Self {
+        //slot zero holds the callee, or the receiver inside methods
+        let slot_zero = match func_type {
+            FuncType::Method | FuncType::Initializer => "this",
+            _ => "",
+        };
+        FunctionState {
+            function: Function { name, ..Function::default() },
+            func_type,
+            locals: vec!(Local {
+                name: slot_zero.to_string(),
+                depth: Some(0),
+                is_captured: false,
+            }),
+            upvalues: vec!(),
+            loops: vec!(),
+            tries: vec!(),
+            scope_depth: 0,
+            too_many_constants: false,
+        }
+    }
+}
+
+//lowers a resolved syntax tree into bytecode for the vm
+pub struct Compiler<'a> {
+    states: Vec<FunctionState>,
+    span: Span, //span of the node currently being lowered
+    status: &'a mut ErrorStatus,
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(status: &'a mut ErrorStatus) -> Self {
+        Compiler {
+            states: vec!(),
+            span: Span::default(),
+            status,
+        }
+    }
+
+    //the script returns the value of its last statement if it is an expression
+    pub fn compile(&mut self, stmts: &[Stmt]) -> Arc<Function> {
+        self.states.push(FunctionState::new(String::new(), FuncType::None));
+        match stmts.split_last() {
+            Some((Stmt::StmtExpr(last), rest)) => {
+                self.compile_stmts(rest);
+                self.compile_expr(&last.expr);
+                self.emit_op(OpCode::Return);
+            },
+            _ => self.compile_stmts(stmts),
+        }
+        self.emit_return();
+        let state = self.states.pop().expect("script state was pushed above");
+        Arc::new(state.function)
+    }
+
+    fn compile_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            stmt.accept(self);
+        }
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.states.last_mut().expect("compiler always has a function state")
+    }
+
+    fn error(&mut self, message: &str) {
+        let error = CompileError::new(self.span.clone(), message);
+        self.status.report_compile_error(error);
+    }
+
+    fn at(&mut self, token: &Token) {
+        self.span = token.span.clone();
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let span = self.span.clone();
+        self.state().function.chunk.write(byte, &span);
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    fn emit_op_with(&mut self, op: OpCode, operand: u8) {
+        self.emit_op(op);
+        self.emit_byte(operand);
+    }
+
+    //indexes past a byte are written as two, after a Wide
+    fn emit_op_with_constant(&mut self, op: OpCode, constant: u16) {
+        match u8::try_from(constant) {
+            Ok(constant) => self.emit_op_with(op, constant),
+            Err(_) => {
+                self.emit_op(OpCode::Wide);
+                self.emit_op(op);
+                for byte in constant.to_be_bytes() {
+                    self.emit_byte(byte);
+                }
+            },
+        }
+    }
+
+    fn emit_return(&mut self) {
+        match self.state().func_type {
+            FuncType::Initializer => self.emit_op_with(OpCode::GetLocal, 0),
+            _ => self.emit_op(OpCode::Nil),
+        }
+        self.emit_op(OpCode::Return);
+    }
+
+    fn make_constant(&mut self, value: Value) -> u16 {
+        let index = self.state().function.chunk.add_constant(value);
+        match u16::try_from(index) {
+            Ok(index) => index,
+            Err(_) => {
+                //every constant after the first one that doesn't fit fails too
+                if !mem::replace(&mut self.state().too_many_constants, true) {
+                    self.error("Too many constants in one chunk.");
+                }
+                0
+            },
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &str) -> u16 {
+        self.make_constant(Value::String(name.to_string()))
+    }
+
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op(op);
+        self.emit_byte(0xff);
+        self.emit_byte(0xff);
+        self.state().function.chunk.code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        let jump = self.state().function.chunk.code.len() - offset - 2;
+        let jump = match u16::try_from(jump) {
+            Ok(jump) => jump,
+            Err(_) => return self.error("Too much code to jump over."),
+        };
+        let code = &mut self.state().function.chunk.code;
+        code[offset..offset + 2].copy_from_slice(&jump.to_be_bytes());
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_op(OpCode::Loop);
+        let jump = self.state().function.chunk.code.len() - loop_start + 2;
+        let jump = match u16::try_from(jump) {
+            Ok(jump) => jump,
+            Err(_) => {
+                self.error("Loop body too large.");
+                0
+            },
+        };
+        for byte in jump.to_be_bytes() {
+            self.emit_byte(byte);
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.state().scope_depth -= 1;
+        loop {
+            let state = self.state();
+            let is_captured = match state.locals.last() {
+                Some(local) if local.depth > Some(state.scope_depth) => local.is_captured,
+                _ => break,
+            };
+            state.locals.pop();
+            match is_captured {
+                true => self.emit_op(OpCode::CloseUpvalue),
+                false => self.emit_op(OpCode::Pop),
+            }
+        }
+    }
+
+    //leaves the scopes inside a loop without forgetting their locals, which
+    //the code after the jump still refers to
+    fn emit_loop_exit(&mut self, keyword: &Token, label: &Option<Rc<Token>>) -> Option<usize> {
+        self.at(keyword);
+        let state = self.state();
+        let index = state.loops.iter().rposition(|lp| match label {
+            Some(label) => lp.label.as_deref() == Some(label.lexeme.as_str()),
+            None => true,
+        })?;
+        self.exit_tries(index + 1);
+        let state = self.state();
+        let depth = state.loops[index].scope_depth;
+        let exits: Vec<bool> = state.locals.iter().rev()
+            .take_while(|local| local.depth.is_some_and(|d| d > depth))
+            .map(|local| local.is_captured)
+            .collect();
+        for is_captured in exits {
+            match is_captured {
+                true => self.emit_op(OpCode::CloseUpvalue),
+                false => self.emit_op(OpCode::Pop),
+            }
+        }
+        Some(index)
+    }
+
+    //leaves the tries begun inside the first `loops` loops, innermost first
+    fn exit_tries(&mut self, loops: usize) {
+        let start = match self.state().tries.iter().position(|tr| tr.loops >= loops) {
+            Some(start) => start,
+            None => return,
+        };
+        let exited = self.state().tries.split_off(start);
+        for (i, tr) in exited.iter().enumerate().rev() {
+            //a finally block can itself return, past the tries outside it only
+            self.state().tries.truncate(start);
+            self.state().tries.extend(exited[..i].iter().cloned());
+            for _ in 0..tr.handlers {
+                self.emit_op(OpCode::PopHandler);
+            }
+            if let Some(finally) = &tr.finally {
+                self.scoped_block(finally);
+            }
+        }
+        self.state().tries.truncate(start);
+        self.state().tries.extend(exited);
+    }
+
+    fn scoped_block(&mut self, stmts: &[Stmt]) {
+        self.begin_scope();
+        self.compile_stmts(stmts);
+        self.end_scope();
+    }
+
+    //compiles code while a value the compiler never named is on top of the
+    //stack, such as a return value, so the slots of locals still line up.
+    //the code must not fall through, as nothing pops the value
+    fn with_hidden_local(&mut self, compile: impl FnOnce(&mut Self)) {
+        self.begin_scope();
+        self.add_local("");
+        self.mark_initialized();
+        compile(self);
+        let state = self.state();
+        state.locals.pop();
+        state.scope_depth -= 1;
+    }
+
+    fn add_local(&mut self, name: &str) {
+        if self.state().locals.len() >= MAX_SLOTS {
+            return self.error("Too many local variables in function.")
+        }
+        self.state().locals.push(Local {
+            name: name.to_string(),
+            depth: None,
+            is_captured: false,
+        });
+    }
+
+    fn mark_initialized(&mut self) {
+        let state = self.state();
+        if state.scope_depth == 0 {return}
+        let depth = state.scope_depth;
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(depth);
+        }
+    }
+
+    //locals are declared as they are reached, globals are late bound by name
+    fn declare_variable(&mut self, name: &str) -> Option<u16> {
+        match self.state().scope_depth {
+            0 => Some(self.identifier_constant(name)),
+            _ => {
+                self.add_local(name);
+                None
+            },
+        }
+    }
+
+    fn define_variable(&mut self, global: Option<u16>) {
+        match global {
+            Some(global) => self.emit_op_with_constant(OpCode::DefineGlobal, global),
+            None => self.mark_initialized(),
+        }
+    }
+
+    fn resolve_local(&self, state: usize, name: &str) -> Option<u8> {
+        self.states[state].locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    fn resolve_upvalue(&mut self, state: usize, name: &str) -> Option<u8> {
+        if state == 0 {return None}
+        if let Some(local) = self.resolve_local(state - 1, name) {
+            self.states[state - 1].locals[local as usize].is_captured = true;
+            return Some(self.add_upvalue(state, local, true))
+        }
+        let upvalue = self.resolve_upvalue(state - 1, name)?;
+        Some(self.add_upvalue(state, upvalue, false))
+    }
+
+    fn add_upvalue(&mut self, state: usize, index: u8, is_local: bool) -> u8 {
+        let upvalues = &self.states[state].upvalues;
+        let existing = upvalues.iter()
+            .position(|upvalue| upvalue.index == index && upvalue.is_local == is_local);
+        if let Some(existing) = existing {
+            return existing as u8
+        }
+        if upvalues.len() >= MAX_SLOTS {
+            self.error("Too many closure variables in function.");
+            return 0
+        }
+        let state = &mut self.states[state];
+        state.upvalues.push(UpvalueRef { index, is_local });
+        state.function.upvalue_count = state.upvalues.len();
+        (state.upvalues.len() - 1) as u8
+    }
+
+    fn named_variable(&mut self, name: &str, assign: bool) {
+        let current = self.states.len() - 1;
+        let (get, set, operand) = if let Some(slot) = self.resolve_local(current, name) {
+            (OpCode::GetLocal, OpCode::SetLocal, slot)
+        } else if let Some(slot) = self.resolve_upvalue(current, name) {
+            (OpCode::GetUpvalue, OpCode::SetUpvalue, slot)
+        } else {
+            let constant = self.identifier_constant(name);
+            let op = match assign {
+                true => OpCode::SetGlobal,
+                false => OpCode::GetGlobal,
+            };
+            return self.emit_op_with_constant(op, constant)
+        };
+        match assign {
+            true => self.emit_op_with(set, operand),
+            false => self.emit_op_with(get, operand),
+        }
+    }
+
+    //a postfix update copies the target's old value below the `targets`
+    //operands of the assignment, so it is what remains once the new value is popped
+    fn keep_old_value(&mut self, update: &expr::Update, targets: u8) {
+        if update.postfix {
+            self.emit_op_with(OpCode::Dup, 0);
+            self.emit_op_with(OpCode::Bury, targets + 1);
+        }
+    }
+
+    fn drop_new_value(&mut self, update: &Option<expr::Update>) {
+        if update.as_ref().is_some_and(|update| update.postfix) {
+            self.emit_op(OpCode::Pop);
+        }
+    }
+
+    fn binary_op(&mut self, operator: &Token) {
+        self.at(operator);
+        let op = match operator.token_type {
+            TokenType::Plus => OpCode::Add,
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Star => OpCode::Multiply,
+            TokenType::Slash => OpCode::Divide,
+            TokenType::StarStar => OpCode::Power,
+            TokenType::TildeSlash => OpCode::IntDivide,
+            TokenType::Percent => OpCode::Modulo,
+            TokenType::Ampersand => OpCode::BitAnd,
+            TokenType::Pipe => OpCode::BitOr,
+            TokenType::Caret => OpCode::BitXor,
+            TokenType::LessLess => OpCode::ShiftLeft,
+            TokenType::GreaterGreater => OpCode::ShiftRight,
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            TokenType::EqualEqual => OpCode::Equal,
+            TokenType::BangEqual => OpCode::NotEqual,
+            _ => return self.error("Unknown binary operator."),
+        };
+        self.emit_op(op);
+    }
+
+    fn function(&mut self, func: &stmt::Func, func_type: FuncType) {
+        let name = func.token.lexeme.to_string();
+        self.states.push(FunctionState::new(name, func_type));
+        self.begin_scope();
+        for param in &func.params {
+            self.add_local(param.lexeme.as_str());
+            self.mark_initialized();
+        }
+        self.state().function.arity = func.params.len();
+        self.compile_stmts(&func.body);
+        self.emit_return();
+
+        let state = self.states.pop().expect("function state was pushed above");
+        let function = VmObject::Function(Arc::new(state.function));
+        let constant = self.make_constant(Value::VmObject(function));
+        self.emit_op_with_constant(OpCode::Closure, constant);
+        for upvalue in state.upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+    }
+}
+
+impl StmtVisitor for Compiler<'_> {
+    type Output = ();
+
+    fn visit_expr_stmt(&mut self, stmt: &stmt::StmtExpr) -> Self::Output {
+        self.compile_expr(&stmt.expr);
+        self.emit_op(OpCode::Pop);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &stmt::Print) -> Self::Output {
+        self.compile_expr(&stmt.expr);
+        self.emit_op(OpCode::Print);
+    }
+
+    fn visit_let_stmt(&mut self, stmt: &stmt::Let) -> Self::Output {
+        self.at(&stmt.token);
+        let global = self.declare_variable(stmt.token.lexeme.as_str());
+        match &stmt.initializer {
+            Some(init) => self.compile_expr(init),
+            None => self.emit_op(OpCode::Nil),
+        }
+        self.define_variable(global);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &stmt::Block) -> Self::Output {
+        self.scoped_block(&stmt.stmts);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &stmt::If) -> Self::Output {
+        self.compile_expr(&stmt.condition);
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        stmt.body.accept(self);
+
+        let else_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::Pop);
+        if let Some(else_body) = &stmt.else_body {
+            else_body.accept(self);
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &stmt::While) -> Self::Output {
+        let loop_start = self.state().function.chunk.code.len();
+        self.compile_expr(&stmt.condition);
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+
+        let scope_depth = self.state().scope_depth;
+        self.state().loops.push(LoopState {
+            label: stmt.label.as_ref().map(|label| label.lexeme.to_string()),
+            scope_depth,
+            breaks: vec!(),
+            continues: vec!(),
+        });
+        stmt.body.accept(self);
+        let lp = self.state().loops.pop().expect("loop state was pushed above");
+
+        for jump in lp.continues {
+            self.patch_jump(jump);
+        }
+        if let Some(increment) = &stmt.increment {
+            self.compile_expr(increment);
+            self.emit_op(OpCode::Pop);
+        }
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::Pop);
+        for jump in lp.breaks {
+            self.patch_jump(jump);
+        }
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &stmt::Break) -> Self::Output {
+        if let Some(index) = self.emit_loop_exit(&stmt.keyword, &stmt.label) {
+            let jump = self.emit_jump(OpCode::Jump);
+            self.state().loops[index].breaks.push(jump);
+        }
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &stmt::Continue) -> Self::Output {
+        if let Some(index) = self.emit_loop_exit(&stmt.keyword, &stmt.label) {
+            let jump = self.emit_jump(OpCode::Jump);
+            self.state().loops[index].continues.push(jump);
+        }
+    }
+
+    fn visit_func_stmt(&mut self, stmt: &stmt::Func) -> Self::Output {
+        self.at(&stmt.token);
+        let global = self.declare_variable(stmt.token.lexeme.as_str());
+        //functions may refer to themselves before their body is compiled
+        self.mark_initialized();
+        self.function(stmt, FuncType::Function);
+        self.define_variable(global);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &stmt::Return) -> Self::Output {
+        self.at(&stmt.keyword);
+        match &stmt.value {
+            Some(value) => self.compile_expr(value),
+            None if matches!(self.state().func_type, FuncType::Initializer) =>
+                self.emit_op_with(OpCode::GetLocal, 0),
+            None => self.emit_op(OpCode::Nil),
+        }
+        if self.state().tries.is_empty() {
+            return self.emit_op(OpCode::Return)
+        }
+        self.with_hidden_local(|compiler| {
+            compiler.exit_tries(0);
+            compiler.at(&stmt.keyword);
+            compiler.emit_op(OpCode::Return);
+        });
+    }
+
+    fn visit_throw_stmt(&mut self, stmt: &stmt::Throw) -> Self::Output {
+        self.compile_expr(&stmt.value);
+        self.at(&stmt.keyword);
+        self.emit_op(OpCode::Throw);
+    }
+
+    //errors jump to the innermost handler with the stack cut back to where
+    //it was installed and the error pushed. a try with a finally installs an
+    //outer handler too, so errors in the catch still run the finally before
+    //being rethrown. the finally block is copied onto each way out
+    fn visit_try_stmt(&mut self, stmt: &stmt::Try) -> Self::Output {
+        self.at(&stmt.keyword);
+        let loops = self.state().loops.len();
+        let finally = &stmt.finally;
+        let rethrow = finally.as_ref().map(|_| self.emit_jump(OpCode::PushHandler));
+        let catch = stmt.catch.as_ref().map(|_| self.emit_jump(OpCode::PushHandler));
+        let handlers = rethrow.iter().count() + catch.iter().count();
+
+        self.state().tries.push(TryState { finally: finally.clone(), handlers, loops });
+        self.scoped_block(&stmt.body);
+        self.state().tries.pop();
+        self.at(&stmt.keyword);
+        for _ in 0..handlers {
+            self.emit_op(OpCode::PopHandler);
+        }
+        if let Some(finally) = finally {
+            self.scoped_block(finally);
+        }
+        let mut ends = vec!(self.emit_jump(OpCode::Jump));
+
+        if let (Some(catch), Some(handler)) = (&stmt.catch, catch) {
+            self.patch_jump(handler);
+            self.begin_scope();
+            self.add_local(catch.name.lexeme.as_str());
+            self.mark_initialized();
+            let handlers = rethrow.iter().count();
+            self.state().tries.push(TryState { finally: finally.clone(), handlers, loops });
+            self.compile_stmts(&catch.body);
+            self.state().tries.pop();
+            self.end_scope();
+            self.at(&stmt.keyword);
+            if let Some(finally) = finally {
+                self.emit_op(OpCode::PopHandler);
+                self.scoped_block(finally);
+            }
+            ends.push(self.emit_jump(OpCode::Jump));
+        }
+
+        //the error stays on top of the stack until the finally rethrows it
+        if let (Some(finally), Some(handler)) = (finally, rethrow) {
+            self.patch_jump(handler);
+            self.with_hidden_local(|compiler| {
+                compiler.scoped_block(finally);
+                compiler.at(&stmt.keyword);
+                compiler.emit_op(OpCode::Throw);
+            });
+        }
+        for end in ends {
+            self.patch_jump(end);
+        }
+    }
+
+    //a from import keeps the module on the stack while each name is read from it
+    fn visit_import_stmt(&mut self, stmt: &stmt::Import) -> Self::Output {
+        self.at(&stmt.path);
+        let path = module_path(&stmt.path).display().to_string();
+        let path = self.make_constant(Value::String(path));
+        self.emit_op_with_constant(OpCode::Import, path);
+        if let Some(alias) = &stmt.alias {
+            self.at(alias);
+            let global = self.declare_variable(alias.lexeme.as_str());
+            return self.define_variable(global)
+        }
+        for name in &stmt.names {
+            self.at(name);
+            let constant = self.identifier_constant(name.lexeme.as_str());
+            self.emit_op_with(OpCode::Dup, 0);
+            self.emit_op_with_constant(OpCode::GetProperty, constant);
+            let global = self.declare_variable(name.lexeme.as_str());
+            self.define_variable(global);
+        }
+        self.emit_op(OpCode::Pop);
+    }
+
+    fn visit_export_stmt(&mut self, stmt: &stmt::Export) -> Self::Output {
+        stmt.declaration.accept(self);
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &stmt::Class) -> Self::Output {
+        self.at(&stmt.token);
+        let name = stmt.token.lexeme.as_str();
+        let name_constant = self.identifier_constant(name);
+        let global = self.declare_variable(name);
+        self.emit_op_with_constant(OpCode::Class, name_constant);
+        self.define_variable(global);
+
+        if let Some(superclass) = &stmt.superclass {
+            self.compile_expr(superclass);
+            self.begin_scope();
+            self.add_local("super");
+            self.mark_initialized();
+
+            self.named_variable(name, false);
+            self.at(&stmt.token);
+            self.emit_op(OpCode::Inherit);
+        }
+
+        self.named_variable(name, false);
+        for method in &stmt.methods {
+            let method = match method {
+                Stmt::Func(func) => func,
+                _ => {
+                    self.error("Undefined method");
+                    continue
+                },
+            };
+            let func_type = match method.token.lexeme.as_str() == "init" {
+                true => FuncType::Initializer,
+                false => FuncType::Method,
+            };
+            let constant = self.identifier_constant(method.token.lexeme.as_str());
+            self.function(method, func_type);
+            self.emit_op_with_constant(OpCode::Method, constant);
+        }
+        self.emit_op(OpCode::Pop);
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+    }
+}
+
+impl ExprVisitor for Compiler<'_> {
+    type Output = ();
+
+    fn visit_literal_expr(&mut self, expr: &expr::Literal) -> Self::Output {
+        let value = expr.value.read().unwrap().clone();
+        match value {
+            Value::Bool(true) => self.emit_op(OpCode::True),
+            Value::Bool(false) => self.emit_op(OpCode::False),
+            Value::None => self.emit_op(OpCode::Nil),
+            value => {
+                let constant = self.make_constant(value);
+                self.emit_op_with_constant(OpCode::Constant, constant);
+            },
+        }
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &expr::Grouping) -> Self::Output {
+        self.compile_expr(&expr.expr);
+    }
+
+    fn visit_unary_expr(&mut self, expr: &expr::Unary) -> Self::Output {
+        self.compile_expr(&expr.right);
+        self.at(&expr.operator);
+        match expr.operator.token_type {
+            TokenType::Minus => self.emit_op(OpCode::Negate),
+            _ => self.emit_op(OpCode::Not),
+        }
+    }
+
+    fn visit_binary_expr(&mut self, expr: &expr::Binary) -> Self::Output {
+        self.compile_expr(&expr.left);
+        self.compile_expr(&expr.right);
+        self.binary_op(&expr.operator);
+    }
+
+    fn visit_var_expr(&mut self, expr: &expr::Var) -> Self::Output {
+        self.at(&expr.token);
+        self.named_variable(expr.token.lexeme.as_str(), false);
+    }
+
+    //`x++` leaves the old value under the assignment, which is popped after
+    fn visit_assign_expr(&mut self, expr: &expr::Assign) -> Self::Output {
+        let name = expr.token.lexeme.as_str();
+        if let Some(update) = &expr.update {
+            self.at(&expr.token);
+            self.named_variable(name, false);
+            if update.postfix {
+                self.named_variable(name, false);
+            }
+        }
+        self.compile_expr(&expr.value);
+        if let Some(update) = &expr.update {
+            self.binary_op(&update.operator);
+        }
+        self.at(&expr.token);
+        self.named_variable(name, true);
+        self.drop_new_value(&expr.update);
+    }
+
+    fn visit_logical_expr(&mut self, expr: &expr::Logical) -> Self::Output {
+        self.compile_expr(&expr.left);
+        let end_jump = match expr.operator.token_type {
+            TokenType::Or => {
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                let end_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(else_jump);
+                end_jump
+            },
+            _ => self.emit_jump(OpCode::JumpIfFalse),
+        };
+        self.emit_op(OpCode::Pop);
+        self.compile_expr(&expr.right);
+        self.patch_jump(end_jump);
+    }
+
+    fn visit_call_expr(&mut self, expr: &expr::Call) -> Self::Output {
+        self.compile_expr(&expr.callee);
+        for arg in &expr.args {
+            self.compile_expr(arg);
+        }
+        self.at(&expr.paren);
+        self.emit_op_with(OpCode::Call, expr.args.len() as u8);
+    }
+
+    fn visit_get_expr(&mut self, expr: &expr::Get) -> Self::Output {
+        self.compile_expr(&expr.object);
+        self.at(&expr.token);
+        let name = self.identifier_constant(expr.token.lexeme.as_str());
+        self.emit_op_with_constant(OpCode::GetProperty, name);
+    }
+
+    fn visit_set_expr(&mut self, expr: &expr::Set) -> Self::Output {
+        self.compile_expr(&expr.object);
+        self.at(&expr.token);
+        let name = self.identifier_constant(expr.token.lexeme.as_str());
+        if let Some(update) = &expr.update {
+            self.emit_op_with(OpCode::Dup, 0);
+            self.emit_op_with_constant(OpCode::GetProperty, name);
+            self.keep_old_value(update, 1);
+        }
+        self.compile_expr(&expr.value);
+        if let Some(update) = &expr.update {
+            self.binary_op(&update.operator);
+        }
+        self.at(&expr.token);
+        self.emit_op_with_constant(OpCode::SetProperty, name);
+        self.drop_new_value(&expr.update);
+    }
+
+    fn visit_list_expr(&mut self, expr: &expr::List) -> Self::Output {
+        for element in &expr.elements {
+            self.compile_expr(element);
+        }
+        self.span = expr.span.clone();
+        self.emit_op_with(OpCode::BuildList, expr.elements.len() as u8);
+    }
+
+    //each part is converted to a string and added to the text before it
+    fn visit_interpolation_expr(&mut self, expr: &expr::Interpolation) -> Self::Output {
+        for (i, part) in expr.parts.iter().enumerate() {
+            self.compile_expr(part);
+            self.span = expr.span.clone();
+            self.emit_op(OpCode::Stringify);
+            if i > 0 {
+                self.emit_op(OpCode::Add);
+            }
+        }
+    }
+
+    fn visit_map_expr(&mut self, expr: &expr::Map) -> Self::Output {
+        for (key, value) in &expr.entries {
+            self.compile_expr(key);
+            self.compile_expr(value);
+        }
+        self.span = expr.span.clone();
+        self.emit_op_with(OpCode::BuildMap, expr.entries.len() as u8);
+    }
+
+    fn visit_lambda_expr(&mut self, expr: &expr::Lambda) -> Self::Output {
+        self.at(&expr.func.token);
+        self.function(&expr.func, FuncType::Function);
+    }
+
+    fn visit_index_expr(&mut self, expr: &expr::Index) -> Self::Output {
+        self.compile_expr(&expr.object);
+        self.compile_expr(&expr.index);
+        self.at(&expr.bracket);
+        self.emit_op(OpCode::GetIndex);
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &expr::SetIndex) -> Self::Output {
+        self.compile_expr(&expr.object);
+        self.compile_expr(&expr.index);
+        if let Some(update) = &expr.update {
+            self.at(&expr.bracket);
+            self.emit_op_with(OpCode::Dup, 1);
+            self.emit_op_with(OpCode::Dup, 1);
+            self.emit_op(OpCode::GetIndex);
+            self.keep_old_value(update, 2);
+        }
+        self.compile_expr(&expr.value);
+        if let Some(update) = &expr.update {
+            self.binary_op(&update.operator);
+        }
+        self.at(&expr.bracket);
+        self.emit_op(OpCode::SetIndex);
+        self.drop_new_value(&expr.update);
+    }
+
+    fn visit_this_expr(&mut self, expr: &expr::This) -> Self::Output {
+        self.at(&expr.keyword);
+        self.named_variable("this", false);
+    }
+
+    fn visit_super_expr(&mut self, expr: &expr::Super) -> Self::Output {
+        self.at(&expr.keyword);
+        let name = self.identifier_constant(expr.method.lexeme.as_str());
+        self.named_variable("this", false);
+        self.named_variable("super", false);
+        self.emit_op_with_constant(OpCode::GetSuper, name);
+    }
+}
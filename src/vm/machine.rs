@@ -0,0 +1,603 @@
+use std::{
+    collections::{HashMap, BTreeMap},
+    mem,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    interpreter::Interpreter,
+    callables::Arity,
+    error::{RuntimeError, TraceFrame},
+    token::{TokenType, Value, Span, MapKey},
+};
+
+use super::{
+    chunk::OpCode,
+    object::{
+        Function, Closure, Upvalue,
+        VmClass, VmInstance, BoundMethod, VmObject,
+    },
+};
+
+const FRAMES_MAX: usize = 256;
+
+//where execution resumes when an error is raised inside a try
+struct Handler {
+    frame: usize,
+    stack_len: usize,
+    ip: usize,
+}
+
+struct CallFrame {
+    closure: Arc<Closure>,
+    ip: usize,
+    slots: usize, //stack index of the frame's slot zero
+}
+
+//executes compiled chunks, sharing globals and output with the interpreter
+pub struct Vm<'a> {
+    interpreter: &'a mut Interpreter,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    open_upvalues: Vec<(usize, Arc<RwLock<Upvalue>>)>,
+    handlers: Vec<Handler>,
+    wide: bool, //the instruction being read follows a Wide
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Vm {
+            interpreter,
+            stack: vec!(),
+            frames: vec!(),
+            open_upvalues: vec!(),
+            handlers: vec!(),
+            wide: false,
+        }
+    }
+
+    pub fn interpret(&mut self, function: Arc<Function>) -> Result<Value, RuntimeError> {
+        let globals = Arc::clone(&self.interpreter.globals);
+        let closure = Arc::new(Closure { function, upvalues: vec!(), globals });
+        self.stack.push(Value::VmObject(VmObject::Closure(Arc::clone(&closure))));
+        self.call_closure(closure, 0)?;
+        let result = self.run();
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+            self.handlers.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<Value, RuntimeError> {
+        loop {
+            match self.dispatch() {
+                Ok(value) => return Ok(value),
+                Err(error) => self.catch(error)?,
+            }
+        }
+    }
+
+    //unwinds to the innermost handler and pushes the error for its catch
+    fn catch(&mut self, error: RuntimeError) -> Result<(), RuntimeError> {
+        let handler = match self.handlers.pop() {
+            Some(handler) => handler,
+            None => return Err(self.traced(error)),
+        };
+        self.frames.truncate(handler.frame + 1);
+        self.close_upvalues(handler.stack_len);
+        self.stack.truncate(handler.stack_len);
+        self.stack.push(error.value());
+        self.frame_mut().ip = handler.ip;
+        Ok(())
+    }
+
+    fn dispatch(&mut self) -> Result<Value, RuntimeError> {
+        loop {
+            let byte = self.read_byte();
+            let op = match OpCode::from_byte(byte) {
+                Some(op) => op,
+                None => return Err(self.error(&format!("Unknown opcode {}.", byte))),
+            };
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.stack.push(constant);
+                },
+                OpCode::Nil => self.stack.push(Value::None),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => {self.pop();},
+                //copies the value `depth` slots below the top
+                OpCode::Dup => {
+                    let depth = self.read_byte() as usize;
+                    self.stack.push(self.peek(depth).clone());
+                },
+                //moves the top value down under the `depth` values beneath it
+                OpCode::Bury => {
+                    let depth = self.read_byte() as usize;
+                    let value = self.pop();
+                    self.stack.insert(self.stack.len() - depth, value);
+                },
+
+                OpCode::GetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack.push(self.stack[slot].clone());
+                },
+                OpCode::SetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                },
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    let value = self.frame().closure.globals.lock().unwrap()
+                        .values.get(&name)
+                        .map(|value| value.read().unwrap().clone());
+                    match value {
+                        Some(value) => self.stack.push(value),
+                        None => {
+                            let message = format!("'{}' cannot be found in current scope.", name);
+                            return Err(self.error(&message))
+                        },
+                    }
+                },
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = Arc::new(RwLock::new(self.pop()));
+                    self.frame().closure.globals.lock().unwrap().define(name, value);
+                },
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    let value = Arc::new(RwLock::new(self.peek(0).clone()));
+                    let globals = Arc::clone(&self.frame().closure.globals);
+                    let mut globals = globals.lock().unwrap();
+                    match globals.values.get_mut(&name) {
+                        Some(global) => *global = value,
+                        None => {
+                            drop(globals);
+                            let message = format!("Cannot assign a value to '{}' before it is declared", name);
+                            return Err(self.error(&message))
+                        },
+                    }
+                },
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = Arc::clone(&self.frame().closure.upvalues[index]);
+                    let value = match &*upvalue.read().unwrap() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                },
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = Arc::clone(&self.frame().closure.upvalues[index]);
+                    let value = self.peek(0).clone();
+                    let mut upvalue = upvalue.write().unwrap();
+                    match &mut *upvalue {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                },
+
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let instance = match self.peek(0) {
+                        Value::VmObject(VmObject::Instance(instance)) => Arc::clone(instance),
+                        Value::Exception(exception) => {
+                            let value = match exception.get(&name) {
+                                Some(value) => value,
+                                None => {
+                                    let message = format!("Undefined property '{}'", name);
+                                    return Err(self.error(&message))
+                                },
+                            };
+                            self.pop();
+                            self.stack.push(value);
+                            continue
+                        },
+                        Value::Module(module) => {
+                            let value = match module.lookup(&name) {
+                                Some(value) => value.read().unwrap().clone(),
+                                None => return Err(self.error(&module.not_exported(&name))),
+                            };
+                            self.pop();
+                            self.stack.push(value);
+                            continue
+                        },
+                        _ => return Err(self.error("Only instances have properties.")),
+                    };
+                    let field = instance.read().unwrap().fields.get(&name).cloned();
+                    let value = match field {
+                        Some(value) => value,
+                        None => {
+                            let class = Arc::clone(&instance.read().unwrap().class);
+                            match self.bind_method(&class, &name) {
+                                Some(method) => method,
+                                None => {
+                                    let message = format!("Undefined property '{}'", name);
+                                    return Err(self.error(&message))
+                                },
+                            }
+                        },
+                    };
+                    self.pop();
+                    self.stack.push(value);
+                },
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let instance = match self.peek(1) {
+                        Value::VmObject(VmObject::Instance(instance)) => Arc::clone(instance),
+                        _ => return Err(self.error("Only instances have fields.")),
+                    };
+                    let value = self.pop();
+                    instance.write().unwrap().fields.insert(name, value.clone());
+                    self.pop();
+                    self.stack.push(value);
+                },
+                OpCode::PushHandler => {
+                    let offset = self.read_short();
+                    let handler = Handler {
+                        frame: self.frames.len() - 1,
+                        stack_len: self.stack.len(),
+                        ip: self.frame().ip + offset,
+                    };
+                    self.handlers.push(handler);
+                },
+                OpCode::PopHandler => {self.handlers.pop();},
+                OpCode::Throw => {
+                    let value = self.pop();
+                    return Err(RuntimeError::thrown(value, self.span()))
+                },
+                OpCode::Wide => self.wide = true,
+                OpCode::Import => {
+                    let path = self.read_string();
+                    let module = self.interpreter.module_at(Path::new(&path))
+                        .map_err(|message| self.error(&message))?;
+                    self.stack.push(Value::Module(module));
+                },
+                OpCode::BuildList => {
+                    let count = self.read_byte() as usize;
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::new_list(elements));
+                },
+                OpCode::BuildMap => {
+                    let count = self.read_byte() as usize;
+                    let values = self.stack.split_off(self.stack.len() - count * 2);
+                    let mut entries = BTreeMap::new();
+                    for pair in values.chunks(2) {
+                        let key = MapKey::from_value(&pair[0])
+                            .map_err(|message| self.error(&message))?;
+                        entries.insert(key, pair[1].clone());
+                    }
+                    self.stack.push(Value::new_map(entries));
+                },
+                OpCode::GetIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    let value = object.index(&index).map_err(|message| self.error(&message))?;
+                    self.stack.push(value);
+                },
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+                    object.set_index(&index, value.clone())
+                        .map_err(|message| self.error(&message))?;
+                    self.stack.push(value);
+                },
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let superclass = match self.pop() {
+                        Value::VmObject(VmObject::Class(class)) => class,
+                        _ => return Err(self.error("super expression cannot be resolved.")),
+                    };
+                    match self.bind_method(&superclass, &name) {
+                        Some(method) => {
+                            self.pop();
+                            self.stack.push(method);
+                        },
+                        None => return Err(self.error("Method not found for 'super'.")),
+                    }
+                },
+
+                OpCode::Equal => self.binary(TokenType::EqualEqual)?,
+                OpCode::NotEqual => self.binary(TokenType::BangEqual)?,
+                OpCode::Greater => self.binary(TokenType::Greater)?,
+                OpCode::GreaterEqual => self.binary(TokenType::GreaterEqual)?,
+                OpCode::Less => self.binary(TokenType::Less)?,
+                OpCode::LessEqual => self.binary(TokenType::LessEqual)?,
+                OpCode::Add => self.binary(TokenType::Plus)?,
+                OpCode::Subtract => self.binary(TokenType::Minus)?,
+                OpCode::Multiply => self.binary(TokenType::Star)?,
+                OpCode::Divide => self.binary(TokenType::Slash)?,
+                OpCode::Power => self.binary(TokenType::StarStar)?,
+                OpCode::IntDivide => self.binary(TokenType::TildeSlash)?,
+                OpCode::Modulo => self.binary(TokenType::Percent)?,
+                OpCode::BitAnd => self.binary(TokenType::Ampersand)?,
+                OpCode::BitOr => self.binary(TokenType::Pipe)?,
+                OpCode::BitXor => self.binary(TokenType::Caret)?,
+                OpCode::ShiftLeft => self.binary(TokenType::LessLess)?,
+                OpCode::ShiftRight => self.binary(TokenType::GreaterGreater)?,
+                OpCode::Not => self.unary(TokenType::Bang)?,
+                OpCode::Negate => self.unary(TokenType::Minus)?,
+                OpCode::Stringify => {
+                    let value = self.pop();
+                    self.stack.push(Value::String(value.to_string()));
+                },
+
+                OpCode::Print => {
+                    let value = self.pop();
+                    let span = self.span();
+                    self.interpreter.print(&value)
+                        .map_err(|error| error.or_at_span(span))?;
+                },
+                OpCode::Jump => {
+                    let offset = self.read_short();
+                    self.frame_mut().ip += offset;
+                },
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_short();
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += offset;
+                    }
+                },
+                OpCode::Loop => {
+                    let offset = self.read_short();
+                    self.frame_mut().ip -= offset;
+                },
+                OpCode::Call => {
+                    let num_args = self.read_byte() as usize;
+                    let callee = self.peek(num_args).clone();
+                    self.call_value(callee, num_args)?;
+                },
+                OpCode::Closure => {
+                    let function = match self.read_constant() {
+                        Value::VmObject(VmObject::Function(function)) => function,
+                        _ => return Err(self.error("Closure operand must be a function.")),
+                    };
+                    let mut upvalues = vec!();
+                    for _ in 0..function.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        let upvalue = match is_local {
+                            true => self.capture_upvalue(self.frame().slots + index),
+                            false => Arc::clone(&self.frame().closure.upvalues[index]),
+                        };
+                        upvalues.push(upvalue);
+                    }
+                    let globals = Arc::clone(&self.frame().closure.globals);
+                    let closure = Closure { function, upvalues, globals };
+                    self.stack.push(Value::VmObject(VmObject::Closure(Arc::new(closure))));
+                },
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                },
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("a frame is always running");
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(result)
+                    }
+                    self.stack.push(result);
+                },
+
+                OpCode::Class => {
+                    let name = self.read_string();
+                    let class = VmClass { name, methods: HashMap::new() };
+                    let class = VmObject::Class(Arc::new(RwLock::new(class)));
+                    self.stack.push(Value::VmObject(class));
+                },
+                OpCode::Inherit => {
+                    let superclass = match self.peek(1) {
+                        Value::VmObject(VmObject::Class(class)) => Arc::clone(class),
+                        _ => return Err(self.error("Superclass must be a class.")),
+                    };
+                    if let Value::VmObject(VmObject::Class(subclass)) = self.peek(0) {
+                        let methods = superclass.read().unwrap().methods.clone();
+                        subclass.write().unwrap().methods.extend(methods);
+                    }
+                    self.pop();
+                },
+                OpCode::Method => {
+                    let name = self.read_string();
+                    let method = match self.pop() {
+                        Value::VmObject(VmObject::Closure(closure)) => closure,
+                        _ => return Err(self.error("Method must be a function.")),
+                    };
+                    if let Value::VmObject(VmObject::Class(class)) = self.peek(0) {
+                        class.write().unwrap().methods.insert(name, method);
+                    }
+                },
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, num_args: usize) -> Result<(), RuntimeError> {
+        let callee_slot = self.stack.len() - num_args - 1;
+        match callee {
+            Value::VmObject(VmObject::Closure(closure)) => self.call_closure(closure, num_args),
+            Value::VmObject(VmObject::BoundMethod(bound)) => {
+                self.stack[callee_slot] = bound.receiver.clone();
+                self.call_closure(Arc::clone(&bound.method), num_args)
+            },
+            Value::VmObject(VmObject::Class(class)) => {
+                let instance = VmInstance {
+                    class: Arc::clone(&class),
+                    fields: HashMap::new(),
+                };
+                let instance = VmObject::Instance(Arc::new(RwLock::new(instance)));
+                self.stack[callee_slot] = Value::VmObject(instance);
+
+                let initializer = class.read().unwrap().methods.get("init").cloned();
+                match initializer {
+                    Some(initializer) => self.call_closure(initializer, num_args),
+                    None if num_args != 0 => {
+                        let message = format!("Expected 0 arguments but got {}.", num_args);
+                        Err(self.error(&message))
+                    },
+                    None => Ok(()),
+                }
+            },
+            //natives and functions declared by the tree walking backend
+            Value::Callable(callable) => {
+                let arity = callable.arity();
+                if !arity.accepts(num_args) {
+                    return Err(self.error(&arity.mismatch(num_args)))
+                }
+                let args = self.stack.split_off(callee_slot + 1)
+                    .into_iter()
+                    .map(|arg| Arc::new(RwLock::new(arg)))
+                    .collect();
+                self.pop();
+                let span = self.span();
+                let result = callable.call(&mut *self.interpreter, args)
+                    .map_err(|error| error.or_at_span(span))?;
+                let result = result.read().unwrap().clone();
+                self.stack.push(result);
+                Ok(())
+            },
+            _ => Err(self.error("Can only call functions and classes.")),
+        }
+    }
+
+    fn call_closure(&mut self, closure: Arc<Closure>, num_args: usize) -> Result<(), RuntimeError> {
+        if num_args != closure.function.arity {
+            let message = Arity::Fixed(closure.function.arity).mismatch(num_args);
+            return Err(self.error(&message))
+        }
+        if self.frames.len() >= FRAMES_MAX {
+            return Err(self.error("Stack overflow."))
+        }
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - num_args - 1,
+        });
+        Ok(())
+    }
+
+    fn bind_method(&self, class: &Arc<RwLock<VmClass>>, name: &str) -> Option<Value> {
+        let method = class.read().unwrap().methods.get(name).cloned()?;
+        let bound = BoundMethod { receiver: self.peek(0).clone(), method };
+        Some(Value::VmObject(VmObject::BoundMethod(Arc::new(bound))))
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Arc<RwLock<Upvalue>> {
+        let existing = self.open_upvalues.iter()
+            .find(|(open_slot, _)| *open_slot == slot);
+        if let Some((_, upvalue)) = existing {
+            return Arc::clone(upvalue)
+        }
+        let upvalue = Arc::new(RwLock::new(Upvalue::Open(slot)));
+        self.open_upvalues.push((slot, Arc::clone(&upvalue)));
+        upvalue
+    }
+
+    //moves every captured variable at or above `last` off the stack
+    fn close_upvalues(&mut self, last: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|(slot, upvalue)| {
+            if *slot < last {return true}
+            *upvalue.write().unwrap() = Upvalue::Closed(stack[*slot].clone());
+            false
+        });
+    }
+
+    fn binary(&mut self, operator: TokenType) -> Result<(), RuntimeError> {
+        let right = self.pop();
+        let left = self.pop();
+        let value = left.binary(&operator, &right)
+            .map_err(|message| self.error(message))?;
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn unary(&mut self, operator: TokenType) -> Result<(), RuntimeError> {
+        let value = self.pop().unary(&operator)
+            .map_err(|message| self.error(message))?;
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("a frame is always running")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("a frame is always running")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_short(&mut self) -> usize {
+        let high = self.read_byte();
+        let low = self.read_byte();
+        u16::from_be_bytes([high, low]) as usize
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = match mem::take(&mut self.wide) {
+            true => self.read_short(),
+            false => self.read_byte() as usize,
+        };
+        self.frame().closure.function.chunk.constants[index].clone()
+    }
+
+    fn read_string(&mut self) -> String {
+        match self.read_constant() {
+            Value::String(name) => name,
+            value => value.to_string(),
+        }
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("compiled code never underflows the stack")
+    }
+
+    fn span(&self) -> Span {
+        match self.frames.last() {
+            Some(frame) => frame.closure.function.chunk.span_at(frame.ip.saturating_sub(1)),
+            None => Span::default(),
+        }
+    }
+
+    //frames are only traced when the error escapes, before they're cleared
+    fn traced(&self, error: RuntimeError) -> RuntimeError {
+        if self.frames.len() < 2 {
+            return error
+        }
+        error.with_trace(|span| {
+            let innermost = self.frames.len() - 1;
+            self.frames.iter().enumerate().rev()
+                .map(|(i, frame)| {
+                    let function = &frame.closure.function;
+                    let span = match (i == innermost, span) {
+                        (true, Some(span)) => span.clone(),
+                        _ => function.chunk.span_at(frame.ip.saturating_sub(1)),
+                    };
+                    TraceFrame::new(&function.name, span)
+                })
+                .collect()
+        })
+    }
+
+    fn error(&self, message: &str) -> RuntimeError {
+        RuntimeError::at_span(self.span(), message)
+    }
+}
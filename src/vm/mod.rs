@@ -0,0 +1,7 @@
+pub mod chunk;
+pub mod compiler;
+pub mod object;
+pub mod machine;
+
+pub use compiler::Compiler;
+pub use machine::Vm;
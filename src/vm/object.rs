@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    fmt,
+};
+
+use crate::{
+    token::Value,
+    environment::Environment,
+};
+
+use super::chunk::Chunk;
+
+//heap objects that only exist while the bytecode vm is running
+#[derive(Debug, Clone)]
+pub enum VmObject {
+    Function(Arc<Function>),
+    Closure(Arc<Closure>),
+    Class(Arc<RwLock<VmClass>>),
+    Instance(Arc<RwLock<VmInstance>>),
+    BoundMethod(Arc<BoundMethod>),
+}
+
+impl VmObject {
+    pub fn upvalue_count(&self) -> usize {
+        match self {
+            VmObject::Function(function) => function.upvalue_count,
+            VmObject::Closure(closure) => closure.function.upvalue_count,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Arc<Function>,
+    pub upvalues: Vec<Arc<RwLock<Upvalue>>>,
+    pub globals: Arc<Mutex<Environment>>, //of the module the closure was created in
+}
+
+//open upvalues point at a live stack slot, closed ones own the value
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct VmClass {
+    pub name: String,
+    pub methods: HashMap<String, Arc<Closure>>,
+}
+
+#[derive(Debug)]
+pub struct VmInstance {
+    pub class: Arc<RwLock<VmClass>>,
+    pub fields: HashMap<String, Value>,
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Arc<Closure>,
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name.is_empty() {
+            true => write!(f, "<script>"),
+            false => write!(f, "<fn {}>", self.name),
+        }
+    }
+}
+
+impl fmt::Display for VmObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmObject::Function(function) => write!(f, "{}", function),
+            VmObject::Closure(closure) => write!(f, "{}", closure.function),
+            VmObject::Class(class) => write!(f, "{}", class.read().unwrap().name),
+            VmObject::Instance(instance) => {
+                let class = Arc::clone(&instance.read().unwrap().class);
+                let name = &class.read().unwrap().name;
+                write!(f, "{} instance", name)
+            },
+            VmObject::BoundMethod(bound) => write!(f, "{}", bound.method.function),
+        }
+    }
+}
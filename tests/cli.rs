@@ -0,0 +1,46 @@
+use std::process::{Command, Output};
+
+//runs the built binary from the crate root
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_first-compiler"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_exit_codes() {
+    let cases: &[(&[&str], i32)] = &[
+        (&["tests/print.lax"], 0),
+        (&["--backend=vm", "tests/print.lax"], 0),
+        (&["tests/errors/top_level_return.lax"], 65),
+        (&["tests/errors/negate_string.lax"], 70),
+        (&["--backend=vm", "tests/errors/negate_string.lax"], 70),
+        (&["tests/missing.lax"], 1),
+        (&["tests/print.lax", "tests/func.lax"], 64),
+        (&["--backend=jit", "tests/print.lax"], 64),
+    ];
+    for (args, code) in cases {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(*code), "{:?}", args);
+    }
+}
+
+#[test]
+fn test_output_streams() {
+    let output = run(&["tests/errors/negate_string.lax"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("error: Operand must be a number.\n"), "{}", stderr);
+}
+
+#[test]
+fn test_json_io_error() {
+    let output = run(&["--error-format=json", "tests/missing.lax"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), concat!(
+        r#"{"severity":"error","phase":"io","code":"io-error","#,
+        r#""message":"Could not read 'tests/missing.lax': No such file or directory (os error 2)","#,
+        r#""file":"tests/missing.lax","span":null,"notes":[]}"#, "\n",
+    ));
+}